@@ -92,7 +92,8 @@ async function handleCustomApiSpecialDetail(id, customApi) {
             method: "GET",
             headers: { 'User-Agent': 'Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/122.0.0.0 Safari/537.36' },
             timeout_secs: 15, // Example timeout
-            response_as_text: true // Indicate we want raw text
+            response_as_text: true, // Indicate we want raw text
+            cache: 'detail'
         };
         
         const rustResponse = await tauriCore.invoke('make_http_request', { options: rustRequestOptions });
@@ -147,7 +148,8 @@ async function handleSpecialSourceDetail(id, sourceCode) {
             method: "GET",
             headers: { 'User-Agent': 'Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/122.0.0.0 Safari/537.36' },
             timeout_secs: 15, // Example timeout
-            response_as_text: true // Indicate we want raw text
+            response_as_text: true, // Indicate we want raw text
+            cache: 'detail'
         };
 
         const rustResponse = await tauriCore.invoke('make_http_request', { options: rustRequestOptions });
@@ -32,7 +32,7 @@ async function executeApiRequest(apiUrl, options = {}) {
             method: method, 
             headers: headers, 
             timeout_secs: timeoutSecs,
-            response_as_text: asText // Pass asText to Rust options
+            response_as_text: true // JSON is parsed from the text body too; false would return base64 bytes
         };
         try {
             const rustResponse = await tauriConstants.invoke('make_http_request', { options: rustOptions });
@@ -7,11 +7,12 @@ async function handleSingleSourceSearch(searchQuery, source, customApi) {
     if (source === 'custom' && !customApi) throw createApiError('使用自定义API时必须提供API地址');
     // API_SITES[source] validation will be implicitly handled by Rust or if Rust command fails.
 
-    let responseDataJsonString;
+    let responseData;
     try {
         // @ts-ignore
         const tauriCore = window.__TAURI__.core;
-        responseDataJsonString = await tauriCore.invoke('search_videos', {
+        // Rust validates the API code, normalizes the items and tags them with source_name/source_code
+        responseData = await tauriCore.invoke('search_videos', {
             query: searchQuery,
             sourceId: source,
             customApiUrl: customApi,
@@ -27,48 +28,9 @@ async function handleSingleSourceSearch(searchQuery, source, customApi) {
         } else if (error && error.message) {
             errorMessage = error.message;
         }
-        throw createApiError(errorMessage, error?.status || 500);
-    }
-
-    let responseData;
-    try {
-        responseData = JSON.parse(responseDataJsonString);
-    } catch (e) {
-        console.error(`Failed to parse JSON response from 'search_videos' for source '${source}':`, responseDataJsonString);
-        throw createApiError(`源 '${source}' 返回的JSON数据无效`, 500);
-    }
-    
-    // Validate the structure of the parsed data (as the original function did)
-    if (responseData.code && responseData.code !== 200 && responseData.code !== 0 && responseData.code !== 1) { // Allow code 1 for some APIs
-        throw createApiError(responseData.msg || `API返回错误代码: ${responseData.code}`, responseData.code);
-    }
-    if (!responseData || typeof responseData.list === 'undefined') {
-        // If code is 1 (often means success but empty list is possible), don't throw if list is missing/empty
-        if (responseData.code !== 1) {
-            throw createApiError('API返回的数据格式无效: 缺少 list 字段', responseData.code || 500);
-        }
-        responseData.list = []; // Ensure list exists if code was 1 and list was missing
-    }
-    if (!Array.isArray(responseData.list)) {
-         // If code is 1 and list is not an array (e.g. null), treat as empty
-        if (responseData.code === 1 && responseData.list === null) {
-            responseData.list = [];
-        } else {
-            throw createApiError('API返回的数据格式无效: list 字段不是数组', responseData.code || 500);
-        }
+        throw createApiError(errorMessage, error?.kind?.status || error?.status || 500);
     }
 
-    // Enrich items with source name and code (this part remains in JS for now)
-    const sourceNameDisplay = source === 'custom' 
-        ? (customApi ? `自定义 (${new URL(customApi).hostname})` : '自定义源') 
-        : (API_SITES[source]?.name || source);
-
-    responseData.list.forEach(item => {
-        item.source_name = sourceNameDisplay;
-        item.source_code = source;
-        if (source === 'custom' && customApi) item.api_url = customApi; // Store the specific custom API URL used
-    });
-
     return { code: 200, list: responseData.list || [] }; // Normalize success code to 200 for the UI
 }
 
@@ -208,7 +208,8 @@ const PLAYER_CONFIG = {
     filterAds: true,  // 是否启用广告过滤
     autoPlayNext: true,  // 默认启用自动连播功能
     adFilteringEnabled: true, // 默认开启分片广告过滤
-    adFilteringStorage: 'adFilteringEnabled' // 存储广告过滤设置的键名
+    adFilteringStorage: 'adFilteringEnabled', // 存储广告过滤设置的键名
+    useNativeProxy: true // 通过 Rust 的 libretv:// 协议加载播放列表和分片（绕过 CORS / Referer 限制）
 };
 
 // 增加错误信息本地化
@@ -63,7 +63,8 @@ async function showDetails(id, vod_name, sourceCode) {
             url: detailApiUrl,
             method: "GET",
             headers: API_CONFIG.detail.headers,
-            timeout_secs: 15 // Example timeout for detail requests
+            timeout_secs: 15, // Example timeout for detail requests
+            cache: 'detail'
         };
         console.log(`[MY_APP_DEBUG_APP_DETAIL] Invoking make_http_request for details:`, JSON.stringify(requestOptions));
 
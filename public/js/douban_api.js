@@ -127,7 +127,8 @@ async function fetchDoubanData(url) {
                 'Accept-Language': 'zh-CN,zh;q=0.9,en;q=0.8',
                 'X-Requested-With': 'XMLHttpRequest'
             },
-            timeout_secs: 20 
+            timeout_secs: 20,
+            cache: 'douban' // 豆瓣标签和榜单变化很慢，缓存数天
         };
         
         if (attempt > 0) {
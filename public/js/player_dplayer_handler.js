@@ -6,8 +6,20 @@
 // Assumes showError, showPositionRestoreHint, setupProgressBarPreciseClicks, saveToHistory, 
 // startProgressSaveInterval, setupLongPressSpeedControl, playNextEpisode are global or imported.
 
+// Routes an m3u8 URL through the libretv:// protocol served by Rust (hls_proxy.rs).
+// convertFileSrc picks libretv://localhost/ or http://libretv.localhost/ depending on the platform.
+function toProxiedHlsUrl(videoUrl, filterAds) {
+    const tauriCore = window.__TAURI__ && window.__TAURI__.core;
+    if (!PLAYER_CONFIG.useNativeProxy || !tauriCore || !/^https?:\/\//i.test(videoUrl)) return null;
+    let proxied = `${tauriCore.convertFileSrc('proxy', 'libretv')}?url=${encodeURIComponent(videoUrl)}`;
+    if (filterAds) proxied += '&filter_ads=1';
+    return proxied;
+}
+
 function initPlayer(videoUrl, sourceCode) {
     if (!videoUrl) return;
+    // Ads are removed in Rust for proxied playlists, so the JS loader is only needed without the proxy
+    const proxiedUrl = toProxiedHlsUrl(videoUrl, adFilteringEnabled);
 
     let dplayerErrorTimeout = null;
     let hlsPlaybackStarted = false; 
@@ -24,7 +36,7 @@ function initPlayer(videoUrl, sourceCode) {
 
     const hlsConfig = {
         debug: false,
-        loader: adFilteringEnabled ? CustomHlsJsLoader : Hls.DefaultConfig.loader,
+        loader: adFilteringEnabled && !proxiedUrl ? CustomHlsJsLoader : Hls.DefaultConfig.loader,
         enableWorker: true, lowLatencyMode: false, backBufferLength: 90,
         maxBufferLength: 30, maxMaxBufferLength: 60, maxBufferSize: 30 * 1000 * 1000,
         maxBufferHole: 0.5, fragLoadingMaxRetry: 6, fragLoadingMaxRetryTimeout: 64000,
@@ -45,7 +57,7 @@ function initPlayer(videoUrl, sourceCode) {
             { text: '问题反馈', click: () => window.open('https://github.com/LibreSpark/LibreTV/issues', '_blank') }
         ],
         video: {
-            url: videoUrl,
+            url: proxiedUrl || videoUrl,
             type: 'hls',
             pic: 'image/nomedia.png',
             customType: {
@@ -490,10 +502,27 @@ class CustomHlsJsLoader extends Hls.DefaultConfig.loader {
             if (context.type === 'manifest' || context.type === 'level') {
                 const onSuccess = callbacks.onSuccess;
                 callbacks.onSuccess = function(response, stats, context) {
-                    if (response.data && typeof response.data === 'string') {
+                    if (!response.data || typeof response.data !== 'string') {
+                        return onSuccess(response, stats, context);
+                    }
+                    const tauriCore = window.__TAURI__ && window.__TAURI__.core;
+                    if (!tauriCore) {
                         response.data = filterAdsFromM3U8(response.data, true);
+                        return onSuccess(response, stats, context);
                     }
-                    return onSuccess(response, stats, context);
+                    // Rust drops the ad segments themselves, not just the discontinuity markers
+                    tauriCore.invoke('filter_m3u8_ads', { content: response.data, playlistUrl: response.url || context.url })
+                        .then(result => {
+                            if (result.report.removed_segments > 0) {
+                                console.log('[AdFilter] removed ad blocks:', result.report.blocks);
+                            }
+                            response.data = result.content;
+                        })
+                        .catch(err => {
+                            console.warn('[AdFilter] filter_m3u8_ads failed, falling back to JS filter:', err);
+                            response.data = filterAdsFromM3U8(response.data, true);
+                        })
+                        .then(() => onSuccess(response, stats, context));
                 };
             }
             load(context, config, callbacks);
@@ -1,3 +1,7 @@
+// 当前搜索的请求组，新搜索开始时取消上一组仍在进行的请求
+let currentSearchGroupId = null;
+let searchGroupCounter = 0;
+
 // 搜索功能 - 修改为支持多选API和多页结果
 async function search() {
     // 密码保护校验
@@ -20,6 +24,13 @@ async function search() {
     }
     
     if (typeof showLoading === 'function') showLoading(); else console.log("Loading..."); // Assuming showLoading is global
+
+    if (currentSearchGroupId && tauriConstants && tauriConstants.invoke) {
+        tauriConstants.invoke('cancel_requests', { groupId: currentSearchGroupId })
+            .catch(error => console.warn('[MY_APP_DEBUG_APP_SEARCH] cancel_requests failed:', error));
+    }
+    const searchGroupId = `search-${Date.now()}-${++searchGroupCounter}`;
+    currentSearchGroupId = searchGroupId;
     
     try {
         // 保存搜索历史
@@ -64,11 +75,12 @@ async function search() {
                     url: apiUrl,
                     method: "GET",
                     headers: API_CONFIG.search.headers,
-                    timeout_secs: Math.floor(AGGREGATED_SEARCH_CONFIG.timeout / 1000) || 8
+                    timeout_secs: Math.floor(AGGREGATED_SEARCH_CONFIG.timeout / 1000) || 8,
+                    cache: 'search' // 重复搜索直接使用 Rust 端缓存
                 };
                 console.log(`[MY_APP_DEBUG_APP_SEARCH] API ${apiId} Invoking make_http_request for main search:`, JSON.stringify(rustRequestOptions));
                 
-                const rustResponse = await tauriConstants.invoke('make_http_request', { options: rustRequestOptions });
+                const rustResponse = await tauriConstants.invoke('make_http_request', { options: rustRequestOptions, groupId: searchGroupId });
                 console.log(`[MY_APP_DEBUG_APP_SEARCH] API ${apiId} Response from Rust. Status: ${rustResponse.status}. Body preview: ${(rustResponse.body || "").substring(0,100)}`);
 
                 if (!(rustResponse.status >= 200 && rustResponse.status < 300)) {
@@ -119,11 +131,12 @@ async function search() {
                                     url: pageUrl,
                                     method: "GET",
                                     headers: API_CONFIG.search.headers,
-                                    timeout_secs: Math.floor(AGGREGATED_SEARCH_CONFIG.timeout / 1000) || 8
+                                    timeout_secs: Math.floor(AGGREGATED_SEARCH_CONFIG.timeout / 1000) || 8,
+                                    cache: 'search'
                                 };
                                 console.log(`[MY_APP_DEBUG_APP_SEARCH] API ${apiId} Invoking make_http_request for page ${page}:`, JSON.stringify(pageRequestOptions));
 
-                                const pageRustResponse = await tauriConstants.invoke('make_http_request', { options: pageRequestOptions });
+                                const pageRustResponse = await tauriConstants.invoke('make_http_request', { options: pageRequestOptions, groupId: searchGroupId });
                                 console.log(`[MY_APP_DEBUG_APP_SEARCH] API ${apiId} Page ${page} Response from Rust. Status: ${pageRustResponse.status}. Body preview: ${(pageRustResponse.body || "").substring(0,100)}`);
 
                                 if (!(pageRustResponse.status >= 200 && pageRustResponse.status < 300)) {
@@ -172,6 +185,8 @@ async function search() {
         });
         
         const resultsArray = await Promise.all(searchPromises);
+        // 已被新的搜索取代，不再渲染旧结果
+        if (searchGroupId !== currentSearchGroupId) return;
         
         resultsArray.forEach(results => {
             if (Array.isArray(results) && results.length > 0) {
@@ -279,6 +294,8 @@ async function search() {
             if (typeof showToast === 'function') showToast('搜索请求失败，请稍后重试', 'error'); else console.error('Search failed');
         }
     } finally {
-        if (typeof hideLoading === 'function') hideLoading(); else console.log("Loading complete."); // Assuming hideLoading is global
+        if (searchGroupId === currentSearchGroupId) {
+            if (typeof hideLoading === 'function') hideLoading(); else console.log("Loading complete."); // Assuming hideLoading is global
+        }
     }
 }
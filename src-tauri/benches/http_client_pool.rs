@@ -0,0 +1,121 @@
+//! Compares a reqwest client built per request (the old `make_http_request` behaviour) with one
+//! shared, pooled client, against a local keep-alive mock server.
+//!
+//! Run with `cargo bench --bench http_client_pool`.
+
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+const REQUESTS: usize = 200;
+const CONCURRENT_BATCH: usize = 20;
+const BODY: &str = r#"{"code":1,"msg":"数据列表","page":1,"pagecount":1,"total":0,"list":[]}"#;
+
+// Minimal HTTP/1.1 server answering every request on a connection with the same JSON body.
+async fn start_mock_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let Ok((mut socket, _)) = listener.accept().await else { continue };
+            tokio::spawn(async move {
+                let mut buf = Vec::new();
+                let mut chunk = [0u8; 4096];
+                loop {
+                    let Ok(read) = socket.read(&mut chunk).await else { return };
+                    if read == 0 {
+                        return;
+                    }
+                    buf.extend_from_slice(&chunk[..read]);
+                    while let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                        buf.drain(..end + 4);
+                        let response = format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: keep-alive\r\n\r\n{}",
+                            BODY.len(),
+                            BODY
+                        );
+                        if socket.write_all(response.as_bytes()).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+            });
+        }
+    });
+    format!("http://{}/api.php/provide/vod/?ac=videolist&wd=test", addr)
+}
+
+fn per_request_client() -> reqwest::Client {
+    reqwest::Client::builder().timeout(Duration::from_secs(20)).build().unwrap()
+}
+
+// Same settings as the app's HttpClient
+fn shared_client() -> reqwest::Client {
+    app_lib::client_builder().build().unwrap()
+}
+
+async fn fetch(client: &reqwest::Client, url: &str) {
+    let response = client.get(url).timeout(Duration::from_secs(20)).send().await.unwrap();
+    assert!(response.status().is_success());
+    response.text().await.unwrap();
+}
+
+fn report(label: &str, total: Duration, requests: usize) {
+    println!(
+        "{:<34} total {:>9.2?}  mean {:>9.2?}/request",
+        label,
+        total,
+        total / requests as u32
+    );
+}
+
+#[tokio::main]
+async fn main() {
+    let url = start_mock_server().await;
+    // Warm up the server and the allocator before measuring
+    fetch(&shared_client(), &url).await;
+
+    let started = Instant::now();
+    for _ in 0..REQUESTS {
+        fetch(&per_request_client(), &url).await;
+    }
+    report("sequential, client per request", started.elapsed(), REQUESTS);
+
+    let client = shared_client();
+    let started = Instant::now();
+    for _ in 0..REQUESTS {
+        fetch(&client, &url).await;
+    }
+    report("sequential, shared client", started.elapsed(), REQUESTS);
+
+    // Bursts like an aggregated search: CONCURRENT_BATCH requests at once
+    let started = Instant::now();
+    for _ in 0..REQUESTS / CONCURRENT_BATCH {
+        let batch: Vec<_> = (0..CONCURRENT_BATCH)
+            .map(|_| {
+                let url = url.clone();
+                tokio::spawn(async move { fetch(&per_request_client(), &url).await })
+            })
+            .collect();
+        for task in batch {
+            task.await.unwrap();
+        }
+    }
+    report("bursts of 20, client per request", started.elapsed(), REQUESTS);
+
+    let started = Instant::now();
+    for _ in 0..REQUESTS / CONCURRENT_BATCH {
+        let batch: Vec<_> = (0..CONCURRENT_BATCH)
+            .map(|_| {
+                let client = client.clone();
+                let url = url.clone();
+                tokio::spawn(async move { fetch(&client, &url).await })
+            })
+            .collect();
+        for task in batch {
+            task.await.unwrap();
+        }
+    }
+    report("bursts of 20, shared client", started.elapsed(), REQUESTS);
+}
@@ -0,0 +1,391 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::m3u8::{MediaPlaylist, MediaSegment, Playlist, Tag};
+use crate::HttpError;
+
+// Ads on collection sites are spliced in as discontinuity-bounded runs of segments served from a
+// different host or directory, named differently and usually cut into shorter segments. Runs that
+// look like the bulk of the video are kept; short runs that stand out are dropped.
+
+/// Runs longer than this are never treated as ads.
+const MAX_AD_RUN_SECS: f64 = 180.0;
+/// Runs that differ only in segment durations must be at most this long to count as ads.
+const MAX_DURATION_ONLY_AD_RUN_SECS: f64 = 30.0;
+/// Relative deviation of a run's typical segment duration from the main content's.
+const DURATION_TOLERANCE: f64 = 0.25;
+
+/// Why a run of segments was considered an ad.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum AdSignal {
+    /// Segments come from another host than the main content.
+    Host,
+    /// Segments live in another directory.
+    Path,
+    /// File names follow another sequence pattern, e.g. `ad0001.ts` vs `xJk9sd0001.ts`. Only
+    /// counts together with another signal.
+    Naming,
+    /// Segment durations differ from the main content's.
+    Duration,
+}
+
+/// A run of segments removed from the playlist.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct RemovedBlock {
+    /// Index of the first removed segment in the original playlist.
+    start_index: usize,
+    segment_count: usize,
+    duration_secs: f64,
+    signals: Vec<AdSignal>,
+    first_uri: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub(crate) struct AdFilterReport {
+    removed_segments: usize,
+    removed_duration_secs: f64,
+    blocks: Vec<RemovedBlock>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct FilteredPlaylist {
+    content: String,
+    report: AdFilterReport,
+}
+
+// What a segment URI looks like, minus the parts that change from segment to segment
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct SegmentShape {
+    host: Option<String>,
+    directory: String,
+    naming: String,
+}
+
+impl SegmentShape {
+    fn of(uri: &str, base: Option<&reqwest::Url>) -> Self {
+        let resolved = reqwest::Url::parse(uri).ok().or_else(|| base.and_then(|base| base.join(uri).ok()));
+        let (host, path) = match &resolved {
+            Some(url) => (url.host_str().map(str::to_string), url.path().to_string()),
+            // Relative URI without a playlist URL to resolve it against
+            None => (None, uri.split(['?', '#']).next().unwrap_or(uri).to_string()),
+        };
+        let (directory, file_name) = path.rsplit_once('/').unwrap_or(("", &path));
+        SegmentShape { host, directory: directory.to_string(), naming: naming_pattern(file_name) }
+    }
+}
+
+// Collapses digit runs, so `xJk9sd0001.ts` and `xJk9sd0002.ts` share the pattern `xJk#sd#.ts`
+fn naming_pattern(file_name: &str) -> String {
+    let mut pattern = String::with_capacity(file_name.len());
+    for c in file_name.chars() {
+        if !c.is_ascii_digit() {
+            pattern.push(c);
+        } else if !pattern.ends_with('#') {
+            pattern.push('#');
+        }
+    }
+    pattern
+}
+
+fn median_duration(segments: &[MediaSegment]) -> f64 {
+    let mut durations: Vec<f64> = segments.iter().map(MediaSegment::duration).collect();
+    durations.sort_by(f64::total_cmp);
+    durations.get(durations.len() / 2).copied().unwrap_or(0.0)
+}
+
+// Segments between two discontinuities, as an index range into the playlist's segments
+struct Run {
+    start: usize,
+    end: usize,
+    shape: SegmentShape,
+    duration: f64,
+}
+
+fn split_runs(playlist: &MediaPlaylist, base: Option<&reqwest::Url>) -> Vec<Run> {
+    let mut runs: Vec<Run> = Vec::new();
+    for (index, segment) in playlist.segments.iter().enumerate() {
+        let shape = SegmentShape::of(&segment.uri, base);
+        match runs.last_mut() {
+            Some(run) if !segment.discontinuity() => {
+                run.end = index + 1;
+                run.duration += segment.duration();
+            }
+            _ => runs.push(Run { start: index, end: index + 1, shape, duration: segment.duration() }),
+        }
+    }
+    runs
+}
+
+/// Removes ad runs from a media playlist and reports what was removed. `base` resolves relative
+/// segment URIs so their hosts can be compared.
+pub(crate) fn remove_ads(playlist: &mut MediaPlaylist, base: Option<&reqwest::Url>) -> AdFilterReport {
+    let mut report = AdFilterReport::default();
+    let runs = split_runs(playlist, base);
+    if runs.len() < 2 {
+        return report;
+    }
+
+    // The shape holding most of the playtime is the main content
+    let mut duration_by_shape: HashMap<&SegmentShape, f64> = HashMap::new();
+    for run in &runs {
+        *duration_by_shape.entry(&run.shape).or_default() += run.duration;
+    }
+    let main_shape = duration_by_shape
+        .into_iter()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(shape, _)| shape.clone())
+        .expect("playlist has runs");
+    let main_segments: Vec<MediaSegment> = runs
+        .iter()
+        .filter(|run| run.shape == main_shape)
+        .flat_map(|run| playlist.segments[run.start..run.end].iter().cloned())
+        .collect();
+    let main_duration = median_duration(&main_segments);
+
+    let mut removed = vec![false; playlist.segments.len()];
+    for run in &runs {
+        if run.shape == main_shape || run.duration > MAX_AD_RUN_SECS {
+            continue;
+        }
+        let mut signals = Vec::new();
+        if run.shape.host != main_shape.host {
+            signals.push(AdSignal::Host);
+        }
+        if run.shape.directory != main_shape.directory {
+            signals.push(AdSignal::Path);
+        }
+        if run.shape.naming != main_shape.naming {
+            signals.push(AdSignal::Naming);
+        }
+        let run_duration = median_duration(&playlist.segments[run.start..run.end]);
+        if main_duration > 0.0 && (run_duration - main_duration).abs() > main_duration * DURATION_TOLERANCE {
+            signals.push(AdSignal::Duration);
+        }
+        // Naming alone proves nothing: hashed or random segment names differ from run to run
+        let is_ad = match signals.as_slice() {
+            [] | [AdSignal::Naming] => false,
+            [AdSignal::Duration] => run.duration <= MAX_DURATION_ONLY_AD_RUN_SECS,
+            _ => true,
+        };
+        if !is_ad {
+            continue;
+        }
+
+        removed[run.start..run.end].fill(true);
+        report.removed_segments += run.end - run.start;
+        report.removed_duration_secs += run.duration;
+        report.blocks.push(RemovedBlock {
+            start_index: run.start,
+            segment_count: run.end - run.start,
+            duration_secs: run.duration,
+            signals,
+            first_uri: playlist.segments[run.start].uri.clone(),
+        });
+    }
+    if report.blocks.is_empty() {
+        return report;
+    }
+
+    // A key or media initialization section declared inside a removed run stays in effect after it,
+    // so the first segment kept after such a run declares the ones it had in the original playlist
+    // again
+    let mut restored_tags: HashMap<usize, Vec<Tag>> = HashMap::new();
+    let (mut key_removed, mut map_removed) = (false, false);
+    for (index, segment) in playlist.segments.iter().enumerate() {
+        if removed[index] {
+            key_removed |= segment.key().is_some();
+            map_removed |= segment.map().is_some();
+            continue;
+        }
+        let mut tags = Vec::new();
+        if std::mem::take(&mut key_removed) && segment.key().is_none() {
+            tags.extend(playlist.key_for(index).cloned().map(Tag::Key));
+        }
+        if std::mem::take(&mut map_removed) && segment.map().is_none() {
+            tags.extend(playlist.map_for(index).cloned().map(Tag::Map));
+        }
+        if !tags.is_empty() {
+            restored_tags.insert(index, tags);
+        }
+    }
+
+    // Drop the removed segments, and the discontinuities that only existed to splice the ads in
+    let mut kept_shape: Option<SegmentShape> = None;
+    let mut after_removed = false;
+    let mut segments = Vec::with_capacity(playlist.segments.len() - report.removed_segments);
+    for (index, mut segment) in std::mem::take(&mut playlist.segments).into_iter().enumerate() {
+        if removed[index] {
+            after_removed = true;
+            continue;
+        }
+        let shape = SegmentShape::of(&segment.uri, base);
+        if after_removed && segment.discontinuity() && kept_shape.as_ref().map_or(true, |previous| *previous == shape) {
+            segment.tags.retain(|tag| *tag != Tag::Discontinuity);
+        }
+        if let Some(tags) = restored_tags.remove(&index) {
+            let at = segment.tags.iter().position(|tag| *tag == Tag::Discontinuity).map_or(0, |position| position + 1);
+            segment.tags.splice(at..at, tags);
+        }
+        after_removed = false;
+        kept_shape = Some(shape);
+        segments.push(segment);
+    }
+    playlist.segments = segments;
+    report
+}
+
+/// Removes ad segments from an HLS media playlist. `playlist_url` (the URL the playlist was loaded
+/// from) lets relative segment URIs be compared by host; master playlists are returned unchanged.
+#[tauri::command]
+pub(crate) fn filter_m3u8_ads(content: String, playlist_url: Option<String>) -> Result<FilteredPlaylist, HttpError> {
+    let mut playlist = Playlist::parse(&content)?;
+    let base = playlist_url.as_deref().and_then(|url| reqwest::Url::parse(url).ok());
+    let report = match &mut playlist {
+        Playlist::Media(media) => remove_ads(media, base.as_ref()),
+        Playlist::Master(_) => AdFilterReport::default(),
+    };
+    if report.blocks.is_empty() {
+        return Ok(FilteredPlaylist { content, report });
+    }
+    log::info!(
+        "[Rust] filter_m3u8_ads removed {} segments ({:.1}s) in {} blocks",
+        report.removed_segments,
+        report.removed_duration_secs,
+        report.blocks.len()
+    );
+    Ok(FilteredPlaylist { content: playlist.to_string(), report })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEDIA_ADS: &str = include_str!("../tests/fixtures/m3u8/media_ads.m3u8");
+    const MEDIA_VOD: &str = include_str!("../tests/fixtures/m3u8/media_vod.m3u8");
+    const MEDIA_LIVE: &str = include_str!("../tests/fixtures/m3u8/media_live.m3u8");
+
+    fn filter(content: &str, playlist_url: Option<&str>) -> FilteredPlaylist {
+        filter_m3u8_ads(content.to_string(), playlist_url.map(str::to_string)).unwrap()
+    }
+
+    #[test]
+    fn removes_discontinuity_bounded_ad_block() {
+        let filtered = filter(MEDIA_ADS, Some("https://v.example.com/20240301/abcd1234/1000kb/hls/index.m3u8"));
+        assert_eq!(filtered.report.removed_segments, 3);
+        assert!((filtered.report.removed_duration_secs - 7.266667).abs() < 1e-9);
+
+        let block = &filtered.report.blocks[0];
+        assert_eq!(block.start_index, 2);
+        assert_eq!(block.signals, [AdSignal::Host, AdSignal::Path, AdSignal::Naming, AdSignal::Duration]);
+        assert_eq!(block.first_uri, "https://ads.example.net/adjump/20240220/ad0001.ts");
+
+        assert!(!filtered.content.contains("ads.example.net"));
+        // Main content around the ad is contiguous again
+        assert!(!filtered.content.contains("#EXT-X-DISCONTINUITY"));
+        let Playlist::Media(playlist) = Playlist::parse(&filtered.content).unwrap() else {
+            panic!("expected a media playlist");
+        };
+        assert_eq!(playlist.segments.len(), 4);
+        assert!(playlist.end_tags.contains(&Tag::EndList));
+    }
+
+    #[test]
+    fn compares_relative_uris_without_playlist_url() {
+        let filtered = filter(MEDIA_ADS, None);
+        assert_eq!(filtered.report.removed_segments, 3);
+    }
+
+    #[test]
+    fn keeps_playlists_without_ads_unchanged() {
+        let filtered = filter(MEDIA_VOD, None);
+        assert!(filtered.report.blocks.is_empty());
+        assert_eq!(filtered.content, MEDIA_VOD);
+
+        // Same shape on both sides of the discontinuity: a real splice, not an ad
+        let filtered = filter(MEDIA_LIVE, None);
+        assert!(filtered.report.blocks.is_empty());
+    }
+
+    #[test]
+    fn keeps_long_runs_from_other_hosts() {
+        let mut content = String::from("#EXTM3U\n#EXT-X-TARGETDURATION:10\n");
+        for index in 0..30 {
+            content.push_str(&format!("#EXTINF:10.0,\nhttps://a.example.com/main/{}.ts\n", index));
+        }
+        content.push_str("#EXT-X-DISCONTINUITY\n");
+        for index in 0..20 {
+            content.push_str(&format!("#EXTINF:10.0,\nhttps://b.example.com/backup/part{}.ts\n", index));
+        }
+        assert!(filter(&content, None).report.blocks.is_empty());
+    }
+
+    #[test]
+    fn keeps_hashed_segment_names_on_the_same_host() {
+        let mut content = String::from("#EXTM3U\n#EXT-X-TARGETDURATION:10\n");
+        for run in 0..4u64 {
+            if run > 0 {
+                content.push_str("#EXT-X-DISCONTINUITY\n");
+            }
+            for index in 0..6u64 {
+                let hash = (run * 6 + index + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+                content.push_str(&format!("#EXTINF:10.0,\nhttps://v.example.com/hls/{:016x}.ts\n", hash));
+            }
+        }
+        let filtered = filter(&content, None);
+        assert!(filtered.report.blocks.is_empty());
+        assert_eq!(filtered.content, content);
+    }
+
+    #[test]
+    fn keeps_keys_declared_on_removed_segments() {
+        let mut content = String::from("#EXTM3U\n#EXT-X-TARGETDURATION:10\n");
+        content.push_str("#EXT-X-KEY:METHOD=AES-128,URI=\"https://v.example.com/hls/key.key\"\n");
+        content.push_str("#EXTINF:3.0,\nhttps://ads.example.net/ad/ad0001.ts\n#EXTINF:3.0,\nhttps://ads.example.net/ad/ad0002.ts\n");
+        content.push_str("#EXT-X-DISCONTINUITY\n");
+        for index in 0..10 {
+            content.push_str(&format!("#EXTINF:10.0,\nhttps://v.example.com/hls/{:04}.ts\n", index));
+        }
+        content.push_str("#EXT-X-ENDLIST\n");
+
+        let filtered = filter(&content, None);
+        assert_eq!(filtered.report.removed_segments, 2);
+        let Playlist::Media(playlist) = Playlist::parse(&filtered.content).unwrap() else {
+            panic!("expected a media playlist");
+        };
+        assert_eq!(playlist.segments[0].uri, "https://v.example.com/hls/0000.ts");
+        assert!(matches!(playlist.segments[0].tags[..], [Tag::Key(_), Tag::Inf { .. }]));
+        let key = playlist.key_for(9).unwrap();
+        assert_eq!(key.attributes.get("URI"), Some("https://v.example.com/hls/key.key"));
+    }
+
+    #[test]
+    fn keeps_init_sections_declared_on_removed_segments() {
+        let mut content = String::from("#EXTM3U\n#EXT-X-VERSION:7\n#EXT-X-TARGETDURATION:10\n");
+        content.push_str("#EXT-X-MAP:URI=\"https://v.example.com/hls/init.mp4\"\n");
+        content.push_str("#EXTINF:3.0,\nhttps://ads.example.net/ad/ad0001.m4s\n#EXTINF:3.0,\nhttps://ads.example.net/ad/ad0002.m4s\n");
+        content.push_str("#EXT-X-DISCONTINUITY\n");
+        for index in 0..10 {
+            content.push_str(&format!("#EXTINF:10.0,\nhttps://v.example.com/hls/{:04}.m4s\n", index));
+        }
+        content.push_str("#EXT-X-ENDLIST\n");
+
+        let filtered = filter(&content, None);
+        assert_eq!(filtered.report.removed_segments, 2);
+        let Playlist::Media(playlist) = Playlist::parse(&filtered.content).unwrap() else {
+            panic!("expected a media playlist");
+        };
+        assert_eq!(playlist.segments[0].uri, "https://v.example.com/hls/0000.m4s");
+        assert!(matches!(playlist.segments[0].tags[..], [Tag::Map(_), Tag::Inf { .. }]));
+        let map = playlist.map_for(9).unwrap();
+        assert_eq!(map.attributes.get("URI"), Some("https://v.example.com/hls/init.mp4"));
+    }
+
+    #[test]
+    fn returns_master_playlists_unchanged() {
+        let master = include_str!("../tests/fixtures/m3u8/master.m3u8");
+        let filtered = filter(master, None);
+        assert_eq!(filtered.content, master);
+        assert!(filtered.report.blocks.is_empty());
+    }
+}
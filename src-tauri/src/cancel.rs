@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tokio::task::AbortHandle;
+
+use crate::{HttpError, HttpErrorKind};
+
+/// In-flight commands started with a `group_id`, so a new search can abort the previous one
+/// instead of waiting for its timeouts. Managed as Tauri state.
+#[derive(Default)]
+pub(crate) struct RequestGroups {
+    next_task_id: AtomicU64,
+    groups: Mutex<HashMap<String, HashMap<u64, AbortHandle>>>,
+}
+
+impl RequestGroups {
+    /// Runs `future` as its own task registered under `group_id`, so `cancel` can abort it; without a
+    /// group it simply runs in place. An aborted task ends with a `Cancelled` error.
+    pub(crate) async fn run<T, F>(&self, group_id: Option<String>, future: F) -> Result<T, HttpError>
+    where
+        T: Send + 'static,
+        F: Future<Output = Result<T, HttpError>> + Send + 'static,
+    {
+        let Some(group_id) = group_id else {
+            return future.await;
+        };
+        let task_id = self.next_task_id.fetch_add(1, Ordering::Relaxed);
+        let task = tokio::spawn(future);
+        self.groups.lock().unwrap().entry(group_id.clone()).or_default().insert(task_id, task.abort_handle());
+
+        let result = task.await;
+        let mut groups = self.groups.lock().unwrap();
+        if let Some(tasks) = groups.get_mut(&group_id) {
+            tasks.remove(&task_id);
+            if tasks.is_empty() {
+                groups.remove(&group_id);
+            }
+        }
+        drop(groups);
+
+        match result {
+            Ok(result) => result,
+            Err(e) if e.is_cancelled() => Err(HttpError::new(HttpErrorKind::Cancelled, "Request cancelled", Some(group_id))),
+            Err(e) => Err(HttpError::new(HttpErrorKind::Other, "Request task failed", Some(e.to_string()))),
+        }
+    }
+
+    /// Aborts every task of `group_id` and returns how many were still running.
+    fn cancel(&self, group_id: &str) -> usize {
+        let tasks = self.groups.lock().unwrap().remove(group_id).unwrap_or_default();
+        for task in tasks.values() {
+            task.abort();
+        }
+        tasks.len()
+    }
+}
+
+/// Aborts the requests started with `group_id` (the `group_id` of `make_http_request`, or
+/// `options.group_id` of the search commands); their invokes reject with a `cancelled` error.
+#[tauri::command]
+pub(crate) fn cancel_requests(group_id: String, groups: tauri::State<'_, RequestGroups>) -> usize {
+    let cancelled = groups.cancel(&group_id);
+    if cancelled > 0 {
+        log::info!("[Rust] cancelled {} requests of group {}", cancelled, group_id);
+    }
+    cancelled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn cancels_running_tasks_of_a_group() {
+        let groups = Arc::new(RequestGroups::default());
+        let slow = |groups: Arc<RequestGroups>, group_id: &str| {
+            let group_id = group_id.to_string();
+            tokio::spawn(async move {
+                groups
+                    .run(Some(group_id), async {
+                        tokio::time::sleep(Duration::from_secs(30)).await;
+                        Ok(())
+                    })
+                    .await
+            })
+        };
+        let first = slow(groups.clone(), "search-1");
+        let second = slow(groups.clone(), "search-1");
+        let other = slow(groups.clone(), "search-2");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        assert_eq!(groups.cancel("search-1"), 2);
+        for task in [first, second] {
+            let error = task.await.unwrap().unwrap_err();
+            assert!(matches!(error.kind, HttpErrorKind::Cancelled));
+        }
+        assert!(!other.is_finished());
+        assert_eq!(groups.cancel("search-1"), 0);
+        assert_eq!(groups.cancel("search-2"), 1);
+    }
+
+    #[tokio::test]
+    async fn forgets_finished_tasks() {
+        let groups = RequestGroups::default();
+        assert_eq!(groups.run(Some("done".to_string()), async { Ok(7) }).await.unwrap(), 7);
+        assert_eq!(groups.run(None, async { Ok(8) }).await.unwrap(), 8);
+        assert!(groups.groups.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,146 @@
+use encoding_rs::{Encoding, GB18030, UTF_8};
+use once_cell::sync::Lazy;
+use regex::bytes::Regex;
+
+// Looks for `<meta charset="gbk">` and `<meta http-equiv="Content-Type" content="text/html; charset=gb2312">`
+static META_CHARSET: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"(?i)<meta[^>]+charset\s*=\s*["']?\s*([A-Za-z0-9_:.-]+)"#).unwrap());
+
+// Browsers only look at the start of the document for the meta tag as well
+const META_SCAN_BYTES: usize = 1024;
+
+/// Whether `label` names an encoding we can decode, e.g. `gbk`, `GB2312` or `utf-8`.
+pub(crate) fn is_known_label(label: &str) -> bool {
+    Encoding::for_label(label.trim().as_bytes()).is_some()
+}
+
+fn content_type_charset(content_type: &str) -> Option<&'static Encoding> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        if !name.trim().eq_ignore_ascii_case("charset") {
+            return None;
+        }
+        Encoding::for_label(value.trim().trim_matches(|c| c == '"' || c == '\'').as_bytes())
+    })
+}
+
+fn meta_charset(body: &[u8]) -> Option<&'static Encoding> {
+    let head = &body[..body.len().min(META_SCAN_BYTES)];
+    let label = META_CHARSET.captures(head)?.get(1)?;
+    Encoding::for_label(label.as_bytes())
+}
+
+fn is_utf8(body: &[u8]) -> bool {
+    std::str::from_utf8(body).is_ok()
+}
+
+fn is_gbk(body: &[u8]) -> bool {
+    GB18030.decode_without_bom_handling_and_without_replacement(body).is_some()
+}
+
+/// Picks the encoding of a response body: the per-source `override_label` wins, then a BOM, the
+/// `Content-Type` charset and a `<meta charset>` in the body. A declared UTF-8 that doesn't
+/// validate is treated as missing, and anything undeclared that isn't valid UTF-8 is read as
+/// GB18030, the superset of GBK and GB2312 most mislabeled Chinese sources use.
+/// Single-byte charsets like iso-8859-1 decode any bytes without errors, and servers often send
+/// them as a default; they only count when the body is neither valid UTF-8 nor valid GBK.
+pub(crate) fn detect(body: &[u8], content_type: Option<&str>, override_label: Option<&str>) -> &'static Encoding {
+    if let Some(encoding) = override_label.and_then(|label| Encoding::for_label(label.trim().as_bytes())) {
+        return encoding;
+    }
+    if let Some((encoding, _)) = Encoding::for_bom(body) {
+        return encoding;
+    }
+    let declared = content_type
+        .and_then(content_type_charset)
+        .into_iter()
+        .chain(meta_charset(body))
+        .find(|&encoding| {
+            if encoding == UTF_8 {
+                is_utf8(body)
+            } else if encoding.is_single_byte() {
+                !is_utf8(body) && !is_gbk(body)
+            } else {
+                true
+            }
+        });
+    match declared {
+        Some(encoding) => encoding,
+        None if is_utf8(body) => UTF_8,
+        None => GB18030,
+    }
+}
+
+/// Decodes a response body to UTF-8 text, see `detect`.
+pub(crate) fn decode(body: &[u8], content_type: Option<&str>, override_label: Option<&str>) -> String {
+    let encoding = detect(body, content_type, override_label);
+    // `decode` also strips a BOM matching the encoding
+    let (text, _, had_errors) = encoding.decode(body);
+    if had_errors {
+        log::warn!("[Rust] response body isn't valid {}, invalid bytes were replaced", encoding.name());
+    }
+    text.into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gbk(text: &str) -> Vec<u8> {
+        GB18030.encode(text).0.into_owned()
+    }
+
+    #[test]
+    fn decodes_charset_from_content_type() {
+        let body = gbk(r#"{"list":[{"vod_name":"三体"}]}"#);
+        assert_eq!(decode(&body, Some("application/json; charset=GBK"), None), r#"{"list":[{"vod_name":"三体"}]}"#);
+        assert_eq!(decode("三体".as_bytes(), Some("text/html; charset=\"utf-8\""), None), "三体");
+    }
+
+    #[test]
+    fn decodes_charset_from_meta_tag() {
+        let html = r#"<html><head><meta http-equiv="Content-Type" content="text/html; charset=gb2312"></head><h1>流浪地球</h1>"#;
+        assert_eq!(decode(&gbk(html), Some("text/html"), None), html);
+
+        let html = r#"<meta charset='gbk'><h1>狂飙</h1>"#;
+        assert_eq!(decode(&gbk(html), None, None), html);
+    }
+
+    #[test]
+    fn handles_bom_mislabels_and_overrides() {
+        let mut body = vec![0xEF, 0xBB, 0xBF];
+        body.extend_from_slice("三体".as_bytes());
+        assert_eq!(decode(&body, Some("text/html; charset=gbk"), None), "三体");
+
+        // Labeled UTF-8, but actually GBK
+        assert_eq!(decode(&gbk("黑暗荣耀"), Some("application/json; charset=utf-8"), None), "黑暗荣耀");
+        // No label at all
+        assert_eq!(decode(&gbk("黑暗荣耀"), None, None), "黑暗荣耀");
+        assert_eq!(decode("plain ascii".as_bytes(), None, None), "plain ascii");
+
+        // Big5 mislabeled as UTF-8 would come out as GB18030 without the override
+        let big5 = encoding_rs::BIG5.encode("臺灣").0.into_owned();
+        assert_eq!(decode(&big5, Some("text/html; charset=utf-8"), Some("big5")), "臺灣");
+    }
+
+    #[test]
+    fn trusts_single_byte_charsets_only_when_gbk_fails() {
+        // Server default charset in front of a GBK body
+        let body = gbk(r#"{"list":[{"vod_name":"三体"}]}"#);
+        assert_eq!(decode(&body, Some("application/json; charset=ISO-8859-1"), None), r#"{"list":[{"vod_name":"三体"}]}"#);
+        let html = r#"<meta charset="iso-8859-1"><h1>狂飙</h1>"#;
+        assert_eq!(decode(&gbk(html), None, None), html);
+        assert_eq!(decode("三体".as_bytes(), Some("text/plain; charset=iso-8859-1"), None), "三体");
+
+        // A trailing lead byte isn't GBK, so the declared charset holds
+        let latin1 = encoding_rs::WINDOWS_1252.encode("café").0.into_owned();
+        assert_eq!(decode(&latin1, Some("text/html; charset=iso-8859-1"), None), "café");
+    }
+
+    #[test]
+    fn recognizes_labels() {
+        assert!(is_known_label("GB2312"));
+        assert!(is_known_label(" gbk "));
+        assert!(!is_known_label("klingon"));
+    }
+}
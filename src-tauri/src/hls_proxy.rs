@@ -0,0 +1,441 @@
+use reqwest::header::{ACCEPT, CONTENT_TYPE, LOCATION, RANGE, REFERER, USER_AGENT};
+use std::future::Future;
+use std::net::IpAddr;
+use std::time::Duration;
+use tauri::http::{header, Request, Response, StatusCode};
+
+use crate::ad_filter;
+use crate::http_client::{HttpClient, BROWSER_USER_AGENT, DEFAULT_TIMEOUT_SECS};
+use crate::m3u8::{self, Playlist};
+
+// --- `libretv://` protocol ---
+//
+// Plan B of Implementation.md: hls.js loads `libretv://localhost/proxy?url=<upstream>` (or
+// `http://libretv.localhost/proxy?...` on Windows and Android) and every request is made from Rust,
+// so CDNs that reject the WebView's CORS requests or need a Referer still play. Playlists are
+// rewritten so their segments, keys and child playlists come back through the scheme as well.
+//
+// Only public hosts are fetched: the responses are readable by any page in the WebView, so
+// loopback and LAN targets would turn the scheme into a proxy into the user's network. Redirects are
+// followed by hand so every hop is checked before anything is sent to it.
+
+pub(crate) const SCHEME: &str = "libretv";
+const PROXY_PATH: &str = "/proxy";
+
+/// Longest wait between two chunks of an upstream body. Large segments on slow CDNs take longer than
+/// any fixed total timeout would allow, but a stalled transfer still fails.
+const READ_IDLE_TIMEOUT: Duration = Duration::from_secs(15);
+/// Redirect hops followed per request, as many as reqwest's default policy allows.
+const MAX_REDIRECTS: usize = 10;
+/// Upper bound for the buffer preallocated from an upstream `Content-Length`.
+const MAX_PREALLOCATED_BYTES: usize = 16 * 1024 * 1024;
+
+// Headers the upstream response may pass on to hls.js
+const FORWARDED_HEADERS: [&str; 3] = ["content-range", "accept-ranges", "cache-control"];
+
+/// Upstream request encoded in a proxy URL's query.
+#[derive(Debug, Clone, PartialEq)]
+struct ProxyTarget {
+    url: reqwest::Url,
+    /// Sent as `Referer`; defaults to the origin of the first playlist and is handed down to
+    /// everything the playlist references.
+    referer: String,
+    /// Run media playlists through the ad filter before rewriting them.
+    filter_ads: bool,
+}
+
+impl ProxyTarget {
+    fn from_request_uri(uri: &str) -> Result<Self, String> {
+        let request_url = reqwest::Url::parse(uri).map_err(|e| format!("invalid request URI: {}", e))?;
+        if request_url.path() != PROXY_PATH {
+            return Err(format!("unknown path: {}", request_url.path()));
+        }
+        let mut url = None;
+        let mut referer = None;
+        let mut filter_ads = false;
+        for (key, value) in request_url.query_pairs() {
+            match key.as_ref() {
+                "url" => url = Some(value.into_owned()),
+                "referer" => referer = Some(value.into_owned()),
+                "filter_ads" => filter_ads = value == "1" || value == "true",
+                _ => {}
+            }
+        }
+        let url = url.ok_or("missing url parameter")?;
+        let url = reqwest::Url::parse(&url).map_err(|e| format!("invalid url parameter: {}", e))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(format!("unsupported scheme: {}", url.scheme()));
+        }
+        if is_local_host(&url) {
+            return Err(format!("local address not allowed: {}", url.host_str().unwrap_or_default()));
+        }
+        let referer = referer.unwrap_or_else(|| format!("{}/", url.origin().ascii_serialization()));
+        Ok(ProxyTarget { url, referer, filter_ads })
+    }
+
+    /// Prefix for `Playlist::resolve_uris`: a proxy URL relative to the scheme's origin, so it works
+    /// whatever form the platform gives custom protocol URLs. `url` goes last for the appended target.
+    fn proxy_prefix(&self) -> String {
+        let filter_ads = if self.filter_ads { "&filter_ads=1" } else { "" };
+        format!("{}?referer={}{}&url=", PROXY_PATH, urlencoding::encode(&self.referer), filter_ads)
+    }
+}
+
+// Loopback, private, link-local and unspecified addresses
+fn is_local_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified() || ip.is_broadcast(),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_local_address(IpAddr::V4(ip)),
+            // fc00::/7 unique local, fe80::/10 link-local
+            None => ip.is_loopback() || ip.is_unspecified() || (ip.segments()[0] & 0xfe00) == 0xfc00 || (ip.segments()[0] & 0xffc0) == 0xfe80,
+        },
+    }
+}
+
+// `localhost` names and local IP literals; names resolving to local addresses are caught by `resolves_locally`
+fn is_local_host(url: &reqwest::Url) -> bool {
+    let Some(host) = url.host_str() else {
+        return true;
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']').trim_end_matches('.').to_ascii_lowercase();
+    match host.parse::<IpAddr>() {
+        Ok(ip) => is_local_address(ip),
+        Err(_) => host == "localhost" || host.ends_with(".localhost"),
+    }
+}
+
+async fn resolves_locally(url: &reqwest::Url) -> bool {
+    let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) else {
+        return true;
+    };
+    match tokio::net::lookup_host((host, port)).await {
+        Ok(mut addresses) => addresses.any(|address| is_local_address(address.ip())),
+        // Names that don't resolve fail in the request itself
+        Err(_) => false,
+    }
+}
+
+async fn is_blocked(url: reqwest::Url) -> bool {
+    is_local_host(&url) || resolves_locally(&url).await
+}
+
+/// Requests `target`, following up to `MAX_REDIRECTS` redirects. `blocked` is asked about the
+/// target and every redirect location before the request to it is sent.
+async fn send_upstream<F, Fut>(
+    http: &HttpClient,
+    target: &ProxyTarget,
+    range: Option<&str>,
+    blocked: F,
+) -> Result<reqwest::Response, (StatusCode, String)>
+where
+    F: Fn(reqwest::Url) -> Fut,
+    Fut: Future<Output = bool>,
+{
+    let mut url = target.url.clone();
+    let mut redirects = 0;
+    loop {
+        if blocked(url.clone()).await {
+            return Err((StatusCode::FORBIDDEN, format!("local address not allowed: {}", url.host_str().unwrap_or_default())));
+        }
+        let mut upstream = http
+            .client_without_redirects(url.as_str(), None)
+            .get(url.clone())
+            .header(USER_AGENT, BROWSER_USER_AGENT)
+            .header(REFERER, &target.referer)
+            .header(ACCEPT, "*/*");
+        // Seeking inside byte-range playlists and large MP4 segments
+        if let Some(range) = range {
+            upstream = upstream.header(RANGE, range);
+        }
+        let response = upstream.send().await.map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
+        let location = response.headers().get(LOCATION).and_then(|value| value.to_str().ok());
+        let (true, Some(location)) = (response.status().is_redirection(), location) else {
+            return Ok(response);
+        };
+        let next = url.join(location).map_err(|e| (StatusCode::BAD_GATEWAY, format!("invalid redirect location: {}", e)))?;
+        if !matches!(next.scheme(), "http" | "https") {
+            return Err((StatusCode::BAD_GATEWAY, format!("unsupported redirect scheme: {}", next.scheme())));
+        }
+        redirects += 1;
+        if redirects > MAX_REDIRECTS {
+            return Err((StatusCode::BAD_GATEWAY, "too many redirects".to_string()));
+        }
+        url = next;
+    }
+}
+
+// Reads the body chunk by chunk, failing when upstream stalls for READ_IDLE_TIMEOUT
+async fn read_body(response: &mut reqwest::Response) -> Result<Vec<u8>, String> {
+    let capacity = response.content_length().map_or(0, |length| (length as usize).min(MAX_PREALLOCATED_BYTES));
+    let mut body = Vec::with_capacity(capacity);
+    loop {
+        match tokio::time::timeout(READ_IDLE_TIMEOUT, response.chunk()).await {
+            Ok(Ok(Some(chunk))) => body.extend_from_slice(&chunk),
+            Ok(Ok(None)) => return Ok(body),
+            Ok(Err(e)) => return Err(e.to_string()),
+            Err(_) => return Err(format!("no data received for {}s", READ_IDLE_TIMEOUT.as_secs())),
+        }
+    }
+}
+
+fn is_playlist(content_type: &str, body: &[u8]) -> bool {
+    let content_type = content_type.to_ascii_lowercase();
+    if content_type.contains("mpegurl") {
+        return true;
+    }
+    let body = body.strip_prefix(b"\xEF\xBB\xBF".as_slice()).unwrap_or(body);
+    let start = body.iter().position(|byte| !byte.is_ascii_whitespace()).unwrap_or(body.len());
+    body[start..].starts_with(b"#EXTM3U")
+}
+
+fn plain_response(status: StatusCode, message: String) -> Response<Vec<u8>> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+        .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+        .body(message.into_bytes())
+        .expect("static response parts are valid")
+}
+
+/// Answers one request of the `libretv://` protocol.
+///
+/// Tauri's asynchronous protocol responder only takes a complete body, so segments can't be passed
+/// through as a stream: they're read into one buffer, bounded by an idle timeout instead of a total
+/// one, and handed over without another copy. Only playlists are parsed and rewritten; ones that
+/// don't parse still get their URIs rewritten line by line.
+pub(crate) async fn handle(http: &HttpClient, request: Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let target = match ProxyTarget::from_request_uri(&request.uri().to_string()) {
+        Ok(target) => target,
+        Err(e) => {
+            log::warn!("[Rust] libretv protocol rejected {}: {}", request.uri(), e);
+            return plain_response(StatusCode::BAD_REQUEST, e);
+        }
+    };
+    let range = request.headers().get(header::RANGE).and_then(|value| value.to_str().ok());
+    // The total timeout only covers the response heads; the body is bounded by READ_IDLE_TIMEOUT
+    let sent = tokio::time::timeout(Duration::from_secs(DEFAULT_TIMEOUT_SECS), send_upstream(http, &target, range, is_blocked));
+    let mut response = match sent.await {
+        Ok(Ok(response)) => response,
+        Ok(Err((status, e))) => {
+            log::warn!("[Rust] libretv protocol failed to fetch {}: {}", target.url, e);
+            return plain_response(status, e);
+        }
+        Err(_) => {
+            log::warn!("[Rust] libretv protocol timed out fetching {}", target.url);
+            return plain_response(StatusCode::GATEWAY_TIMEOUT, "upstream timed out".to_string());
+        }
+    };
+    // Relative URIs are relative to where the playlist ended up, e.g. index.m3u8 redirecting to another CDN
+    let final_url = response.url().clone();
+    let status = response.status().as_u16();
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("application/octet-stream")
+        .to_string();
+    let forwarded: Vec<(&str, String)> = FORWARDED_HEADERS
+        .iter()
+        .filter_map(|name| Some((*name, response.headers().get(*name)?.to_str().ok()?.to_string())))
+        .collect();
+    let body = match read_body(&mut response).await {
+        Ok(body) => body,
+        Err(e) => {
+            log::warn!("[Rust] libretv protocol failed to read {}: {}", target.url, e);
+            return plain_response(StatusCode::BAD_GATEWAY, e.to_string());
+        }
+    };
+
+    let mut builder = Response::builder().status(status).header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*");
+    if (200..300).contains(&status) && is_playlist(&content_type, &body) {
+        let text = String::from_utf8_lossy(&body);
+        let rewritten = match Playlist::parse(&text) {
+            Ok(mut playlist) => {
+                if let (true, Playlist::Media(media)) = (target.filter_ads, &mut playlist) {
+                    ad_filter::remove_ads(media, Some(&final_url));
+                }
+                playlist.resolve_uris(&final_url, Some(&target.proxy_prefix()));
+                playlist.to_string()
+            }
+            // Passed through as is, its relative URIs would point at the scheme's origin
+            Err(e) => {
+                log::warn!("[Rust] libretv protocol rewriting unparsable playlist {} line by line: {:?}", target.url, e.details);
+                m3u8::resolve_uris_in_text(&text, &final_url, Some(&target.proxy_prefix()))
+            }
+        };
+        return builder
+            .header(header::CONTENT_TYPE, "application/vnd.apple.mpegurl")
+            .body(rewritten.into_bytes())
+            .expect("playlist response parts are valid");
+    }
+
+    builder = builder.header(header::CONTENT_TYPE, content_type);
+    for (name, value) in forwarded {
+        builder = builder.header(name, value);
+    }
+    builder.body(body).unwrap_or_else(|e| plain_response(StatusCode::BAD_GATEWAY, e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::m3u8::Tag;
+
+    const MEDIA_ENCRYPTED: &str = include_str!("../tests/fixtures/m3u8/media_encrypted.m3u8");
+    const MASTER: &str = include_str!("../tests/fixtures/m3u8/master.m3u8");
+
+    fn target(upstream: &str) -> ProxyTarget {
+        let uri = format!("libretv://localhost/proxy?url={}", urlencoding::encode(upstream));
+        ProxyTarget::from_request_uri(&uri).unwrap()
+    }
+
+    #[test]
+    fn parses_proxy_urls_of_every_platform() {
+        let upstream = "https://cdn.example.com/vod/index.m3u8?token=a&b=c";
+        let parsed = target(upstream);
+        assert_eq!(parsed.url.as_str(), upstream);
+        assert_eq!(parsed.referer, "https://cdn.example.com/");
+        assert!(!parsed.filter_ads);
+
+        let windows = format!(
+            "http://libretv.localhost/proxy?url={}&referer={}&filter_ads=1",
+            urlencoding::encode(upstream),
+            urlencoding::encode("https://site.example.org/")
+        );
+        let parsed = ProxyTarget::from_request_uri(&windows).unwrap();
+        assert_eq!(parsed.url.as_str(), upstream);
+        assert_eq!(parsed.referer, "https://site.example.org/");
+        assert!(parsed.filter_ads);
+
+        assert!(ProxyTarget::from_request_uri("libretv://localhost/proxy").is_err());
+        assert!(ProxyTarget::from_request_uri("libretv://localhost/other?url=https%3A%2F%2Fa.b").is_err());
+        assert!(ProxyTarget::from_request_uri("libretv://localhost/proxy?url=file%3A%2F%2F%2Fetc%2Fpasswd").is_err());
+    }
+
+    #[test]
+    fn rewrites_segments_and_keys_through_the_scheme() {
+        let target = target("https://cdn.example.com/vod/abc/index.m3u8");
+        let mut playlist = Playlist::parse(MEDIA_ENCRYPTED).unwrap();
+        playlist.resolve_uris(&target.url, Some(&target.proxy_prefix()));
+        let text = playlist.to_string();
+
+        let prefix = "/proxy?referer=https%3A%2F%2Fcdn.example.com%2F&url=";
+        assert!(text.contains(&format!("\n{}https%3A%2F%2Fcdn.example.com%2Fvod%2Fabc%2Fseg120.ts\n", prefix)));
+        assert!(text.contains(&format!(r#"URI="{}https%3A%2F%2Fkeys.example.com%2Fkey1.key""#, prefix)));
+        assert!(text.contains(&format!(r#"URI="{}https%3A%2F%2Fcdn.example.com%2Fvod%2Fabc%2Fkey2.key""#, prefix)));
+        assert!(text.contains("#EXT-X-KEY:METHOD=NONE\n"));
+
+        // Proxied URIs parse back into the same target
+        let segment = format!("libretv://localhost{}https%3A%2F%2Fcdn.example.com%2Fvod%2Fabc%2Fseg120.ts", prefix);
+        let parsed = ProxyTarget::from_request_uri(&segment).unwrap();
+        assert_eq!(parsed.url.as_str(), "https://cdn.example.com/vod/abc/seg120.ts");
+        assert_eq!(parsed.referer, target.referer);
+    }
+
+    #[test]
+    fn rewrites_variants_of_master_playlists() {
+        let target = target("https://cdn.example.com/vod/master.m3u8");
+        let mut playlist = Playlist::parse(MASTER).unwrap();
+        playlist.resolve_uris(&target.url, Some(&target.proxy_prefix()));
+        let Playlist::Master(master) = playlist else {
+            panic!("expected a master playlist");
+        };
+        let uris: Vec<&str> = master.variants().map(|variant| variant.uri.as_str()).collect();
+        assert!(uris[0].ends_with("&url=https%3A%2F%2Fcdn.example.com%2Fvod%2F360p%2Findex.m3u8"));
+        assert!(uris[2].ends_with("&url=https%3A%2F%2Fcdn.example.com%2Fvod%2F1080p%2Findex.m3u8%3Ftoken%3Dabc"));
+        let mut rendition_uris = master.tags.iter().filter_map(|tag| match tag {
+            Tag::Media(attributes) => Some(attributes.get("URI")),
+            _ => None,
+        });
+        assert!(rendition_uris.all(|uri| uri.is_some_and(|uri| uri.starts_with("/proxy?"))));
+    }
+
+    #[test]
+    fn rejects_local_targets() {
+        let local = [
+            "http://127.0.0.1:8080/index.m3u8",
+            "http://localhost/index.m3u8",
+            "http://router.localhost/index.m3u8",
+            "http://192.168.1.1/admin",
+            "http://10.0.0.5/seg.ts",
+            "http://172.16.3.4/seg.ts",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://0.0.0.0/",
+            "http://[::1]/index.m3u8",
+            "http://[fd00::1]/index.m3u8",
+            "http://[fe80::1]/index.m3u8",
+            "http://[::ffff:192.168.0.1]/index.m3u8",
+        ];
+        for upstream in local {
+            let uri = format!("libretv://localhost/proxy?url={}", urlencoding::encode(upstream));
+            assert!(ProxyTarget::from_request_uri(&uri).is_err(), "{} was accepted", upstream);
+        }
+        assert!(!is_local_host(&reqwest::Url::parse("https://8.8.8.8/index.m3u8").unwrap()));
+        assert!(!is_local_host(&reqwest::Url::parse("https://[2001:db8::1]/index.m3u8").unwrap()));
+        assert!(!is_local_host(&reqwest::Url::parse("https://cdn.example.com/index.m3u8").unwrap()));
+    }
+
+    // Answers every request with `response` and counts the requests it got
+    async fn mock_server(response: String) -> (u16, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let hits = std::sync::Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let response = response.clone();
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 1024];
+                    let _ = socket.read(&mut buf).await;
+                    let _ = socket.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        (port, hits)
+    }
+
+    fn redirect_to(location: &str) -> String {
+        format!("HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", location)
+    }
+
+    #[tokio::test]
+    async fn checks_every_redirect_hop_before_requesting_it() {
+        use std::sync::atomic::Ordering;
+
+        let http = HttpClient::new().unwrap();
+        let (internal_port, internal_hits) = mock_server("HTTP/1.1 200 OK\r\nContent-Length: 6\r\nConnection: close\r\n\r\nsecret".to_string()).await;
+        // Stands in for a public host: the only one `blocked` lets through
+        let (public_port, _) = mock_server(redirect_to(&format!("http://127.0.0.1:{}/admin", internal_port))).await;
+        let blocked = move |url: reqwest::Url| async move { url.port() != Some(public_port) };
+
+        let target = ProxyTarget {
+            url: reqwest::Url::parse(&format!("http://127.0.0.1:{}/index.m3u8", public_port)).unwrap(),
+            referer: String::new(),
+            filter_ads: false,
+        };
+        let (status, _) = send_upstream(&http, &target, None, blocked).await.unwrap_err();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(internal_hits.load(Ordering::SeqCst), 0);
+
+        // Redirect locations go through the same check as the target itself
+        let (named_port, _) = mock_server(redirect_to(&format!("http://localhost:{}/admin", internal_port))).await;
+        let target = ProxyTarget { url: reqwest::Url::parse(&format!("http://127.0.0.1:{}/index.m3u8", named_port)).unwrap(), ..target };
+        let blocked = move |url: reqwest::Url| async move { url.port() != Some(named_port) && is_blocked(url).await };
+        let (status, _) = send_upstream(&http, &target, None, blocked).await.unwrap_err();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(internal_hits.load(Ordering::SeqCst), 0);
+        assert!(is_blocked(reqwest::Url::parse("http://localhost./x").unwrap()).await);
+    }
+
+    #[test]
+    fn detects_playlists_by_type_or_content() {
+        assert!(is_playlist("application/vnd.apple.mpegURL", b""));
+        assert!(is_playlist("text/plain", b"\xEF\xBB\xBF\n#EXTM3U\n"));
+        assert!(!is_playlist("video/mp2t", b"\x47\x40\x00"));
+    }
+}
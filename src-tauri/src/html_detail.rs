@@ -0,0 +1,246 @@
+use regex::Regex;
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+
+use crate::{HttpError, HttpErrorKind};
+
+// Defaults follow the regexes handleSpecialSourceDetail used in apiDetailHandlers.js
+const DEFAULT_TITLE_SELECTOR: &str = "h1";
+const DEFAULT_DESCRIPTION_SELECTOR: &str = ".sketch";
+const DEFAULT_COVER_SELECTOR: &str = r#"meta[property="og:image"], img[data-original]"#;
+const DEFAULT_EPISODE_PATTERN: &str = r#"\$(https?://[^"'\s<]+?\.m3u8)"#;
+
+/// Per-source rules for scraping an HTML detail page, stored on `ApiSourceInfo`.
+/// Every field is optional and falls back to the MacCMS template defaults above.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub(crate) struct HtmlDetailRules {
+    pub(crate) title_selector: Option<String>,
+    pub(crate) cover_selector: Option<String>,
+    pub(crate) description_selector: Option<String>,
+    /// Elements holding episodes; without it `episode_pattern` runs over the whole page.
+    pub(crate) episode_selector: Option<String>,
+    /// Attribute to read from `episode_selector` matches instead of their text, e.g. `href`.
+    pub(crate) episode_attr: Option<String>,
+    /// Regex whose first capture group (or whole match) is the episode URL.
+    pub(crate) episode_pattern: Option<String>,
+}
+
+/// Data scraped from an HTML detail page.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub(crate) struct HtmlDetail {
+    pub(crate) title: String,
+    pub(crate) cover: String,
+    pub(crate) desc: String,
+    pub(crate) episodes: Vec<String>,
+}
+
+fn invalid_rule(kind: &str, value: &str, reason: String) -> HttpError {
+    HttpError::new(
+        HttpErrorKind::InvalidConfig,
+        "Invalid source configuration",
+        Some(format!("Invalid {} '{}': {}", kind, value, reason)),
+    )
+}
+
+fn parse_selector(selector: &str) -> Result<Selector, HttpError> {
+    Selector::parse(selector).map_err(|e| invalid_rule("CSS selector", selector, e.to_string()))
+}
+
+fn parse_pattern(pattern: &str) -> Result<Regex, HttpError> {
+    Regex::new(pattern).map_err(|e| invalid_rule("regex", pattern, e.to_string()))
+}
+
+impl HtmlDetailRules {
+    /// Checks that every configured selector and regex compiles.
+    pub(crate) fn validate(&self) -> Result<(), HttpError> {
+        for selector in [&self.title_selector, &self.cover_selector, &self.description_selector, &self.episode_selector]
+            .into_iter()
+            .flatten()
+        {
+            parse_selector(selector)?;
+        }
+        if let Some(pattern) = &self.episode_pattern {
+            parse_pattern(pattern)?;
+        }
+        Ok(())
+    }
+}
+
+// Collapses whitespace of all text nodes below `element`, the way `replace(/<[^>]+>/g, ' ').trim()` did in JS.
+fn element_text(element: ElementRef) -> String {
+    element.text().collect::<String>().split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn first_text(document: &Html, selector: &str) -> Result<String, HttpError> {
+    let selector = parse_selector(selector)?;
+    Ok(document.select(&selector).map(element_text).find(|text| !text.is_empty()).unwrap_or_default())
+}
+
+fn first_image(document: &Html, selector: &str, page_url: Option<&reqwest::Url>) -> Result<String, HttpError> {
+    let selector = parse_selector(selector)?;
+    let cover = document
+        .select(&selector)
+        .find_map(|element| {
+            let value = element.value();
+            ["content", "data-original", "data-src", "src"]
+                .iter()
+                .find_map(|attr| value.attr(attr))
+                .map(str::trim)
+                .filter(|url| !url.is_empty())
+        })
+        .unwrap_or_default();
+    // Covers are often site-relative, so resolve them against the detail page
+    Ok(match page_url.and_then(|base| base.join(cover).ok()) {
+        Some(url) if !cover.is_empty() => url.to_string(),
+        _ => cover.to_string(),
+    })
+}
+
+fn captured_urls<'a>(pattern: &'a Regex, haystack: &'a str) -> impl Iterator<Item = String> + 'a {
+    pattern.captures_iter(haystack).filter_map(|captures| {
+        let url = captures.get(1).or_else(|| captures.get(0))?.as_str();
+        let url = url.trim_start_matches('$');
+        // Some pages append "(1080P)" style suffixes to the link
+        let url = url.split('(').next().unwrap_or(url).trim();
+        Some(url.to_string())
+    })
+}
+
+fn extract_episodes(document: &Html, html: &str, rules: &HtmlDetailRules) -> Result<Vec<String>, HttpError> {
+    let default_pattern = parse_pattern(DEFAULT_EPISODE_PATTERN)?;
+    let pattern = match &rules.episode_pattern {
+        Some(pattern) => parse_pattern(pattern)?,
+        None => default_pattern.clone(),
+    };
+
+    let mut found: Vec<String> = match &rules.episode_selector {
+        Some(selector) => {
+            let selector = parse_selector(selector)?;
+            let mut urls = Vec::new();
+            for element in document.select(&selector) {
+                let value = match &rules.episode_attr {
+                    Some(attr) => element.value().attr(attr).unwrap_or_default().to_string(),
+                    None => element_text(element),
+                };
+                if value.starts_with("http://") || value.starts_with("https://") {
+                    urls.push(value);
+                } else {
+                    urls.extend(captured_urls(&pattern, &value));
+                }
+            }
+            urls
+        }
+        None => captured_urls(&pattern, html).collect(),
+    };
+    // A too specific per-source pattern shouldn't leave the user without episodes
+    if found.is_empty() && rules.episode_pattern.is_some() {
+        found = captured_urls(&default_pattern, html).collect();
+    }
+
+    let mut episodes = Vec::new();
+    for url in found {
+        if (url.starts_with("http://") || url.starts_with("https://")) && !episodes.contains(&url) {
+            episodes.push(url);
+        }
+    }
+    Ok(episodes)
+}
+
+/// Scrapes title, cover, description and episode URLs out of a detail page.
+/// `page_url` is used to resolve relative cover URLs.
+pub(crate) fn extract_html_detail(html: &str, rules: &HtmlDetailRules, page_url: Option<&str>) -> Result<HtmlDetail, HttpError> {
+    let document = Html::parse_document(html);
+    let page_url = page_url.and_then(|url| reqwest::Url::parse(url).ok());
+
+    Ok(HtmlDetail {
+        title: first_text(&document, rules.title_selector.as_deref().unwrap_or(DEFAULT_TITLE_SELECTOR))?,
+        cover: first_image(&document, rules.cover_selector.as_deref().unwrap_or(DEFAULT_COVER_SELECTOR), page_url.as_ref())?,
+        desc: first_text(&document, rules.description_selector.as_deref().unwrap_or(DEFAULT_DESCRIPTION_SELECTOR))?,
+        episodes: extract_episodes(&document, html, rules)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FFZY_DETAIL: &str = include_str!("../tests/fixtures/html/ffzy_detail.html");
+    const HEIMUER_DETAIL: &str = include_str!("../tests/fixtures/html/heimuer_detail.html");
+    const PLAYLIST_DETAIL: &str = include_str!("../tests/fixtures/html/playlist_links_detail.html");
+
+    fn ffzy_rules() -> HtmlDetailRules {
+        HtmlDetailRules {
+            episode_pattern: Some(r#"\$(https?://[^"'\s]+?/\d{8}/\d+_[a-f0-9]+/index\.m3u8)"#.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn extracts_ffzy_detail_with_source_pattern() {
+        let detail = extract_html_detail(FFZY_DETAIL, &ffzy_rules(), Some("http://ffzy5.tv/index.php/vod/detail/id/1234.html")).unwrap();
+        assert_eq!(detail.title, "三体");
+        assert_eq!(detail.cover, "http://ffzy5.tv/upload/vod/20230115/santi.jpg");
+        assert_eq!(detail.desc, "地球文明向宇宙发出的第一声啼鸣，取得了探寻外星文明的突破性进展。");
+        assert_eq!(
+            detail.episodes,
+            vec![
+                "https://svipsvip.ffzy-online5.com/20230115/1001_a1b2c3d4/index.m3u8",
+                "https://svipsvip.ffzy-online5.com/20230116/1002_b2c3d4e5/index.m3u8",
+                "https://svipsvip.ffzy-online5.com/20230117/1003_c3d4e5f6/index.m3u8",
+            ]
+        );
+    }
+
+    #[test]
+    fn extracts_heimuer_detail_with_defaults() {
+        let detail = extract_html_detail(HEIMUER_DETAIL, &HtmlDetailRules::default(), Some("https://heimuer.tv/index.php/vod/detail/id/88.html")).unwrap();
+        assert_eq!(detail.title, "流浪地球2");
+        assert_eq!(detail.cover, "https://img.heimuer.tv/cover/88.jpg");
+        assert_eq!(detail.desc, "太阳即将毁灭，人类在地球表面建造出巨大的推进器。");
+        // Duplicates are dropped and "(HD)" suffixes stripped
+        assert_eq!(
+            detail.episodes,
+            vec!["https://hmrvideo.example.com/play/abc/index.m3u8", "https://hmrvideo.example.com/play/def/index.m3u8"]
+        );
+    }
+
+    #[test]
+    fn extracts_episodes_from_configured_elements() {
+        let rules = HtmlDetailRules {
+            title_selector: Some(".video-title".to_string()),
+            cover_selector: Some(".poster img".to_string()),
+            description_selector: Some("#intro".to_string()),
+            episode_selector: Some("ul.playlist a".to_string()),
+            episode_attr: Some("data-url".to_string()),
+            episode_pattern: None,
+        };
+        let detail = extract_html_detail(PLAYLIST_DETAIL, &rules, Some("https://example.org/vod/7.html")).unwrap();
+        assert_eq!(detail.title, "狂飙 第一季");
+        assert_eq!(detail.cover, "https://example.org/posters/7.png");
+        assert_eq!(detail.desc, "扫黑除恶 二十年");
+        assert_eq!(detail.episodes, vec!["https://cdn.example.org/7/1.m3u8", "https://cdn.example.org/7/2.m3u8"]);
+    }
+
+    #[test]
+    fn falls_back_to_generic_pattern_when_source_pattern_misses() {
+        let detail = extract_html_detail(HEIMUER_DETAIL, &ffzy_rules(), None).unwrap();
+        assert_eq!(detail.episodes.len(), 2);
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        let rules = HtmlDetailRules {
+            title_selector: Some("h1[".to_string()),
+            ..Default::default()
+        };
+        assert!(rules.validate().is_err());
+        assert!(extract_html_detail(FFZY_DETAIL, &rules, None).is_err());
+
+        let rules = HtmlDetailRules {
+            episode_pattern: Some("(unclosed".to_string()),
+            ..Default::default()
+        };
+        assert!(rules.validate().is_err());
+    }
+}
@@ -0,0 +1,494 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::http_client::HttpClient;
+use crate::{HttpError, HttpErrorKind, HttpRequestOptions, HttpResponse};
+
+// --- On-disk response cache ---
+//
+// Responses of requests that opt in with `HttpRequestOptions.cache` are stored as one JSON file per
+// request in the app cache dir. Each category has its own lifetime: a response is served as is while
+// fresh, served and refreshed in the background while stale, and fetched again after that. The
+// least recently used files are dropped once the cache grows past its size limit. File access runs
+// on tokio's blocking pool, never on the workers serving the search fan-out.
+
+/// Directory inside the app cache dir.
+pub(crate) const CACHE_DIR_NAME: &str = "http-cache";
+const DEFAULT_MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+const MINUTE: u64 = 60;
+const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 24 * HOUR;
+
+// Request headers that can change the response and are part of the cache key
+const KEY_HEADERS: [&str; 5] = ["accept", "accept-language", "authorization", "cookie", "referer"];
+// Key headers carrying credentials; only their SHA-256 goes into the key, which is written to disk
+const SECRET_HEADERS: [&str; 2] = ["authorization", "cookie"];
+
+/// What a cached request is for, which decides how long its response stays usable.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum CacheCategory {
+    Search,
+    Detail,
+    Douban,
+}
+
+impl CacheCategory {
+    const ALL: [CacheCategory; 3] = [CacheCategory::Search, CacheCategory::Detail, CacheCategory::Douban];
+
+    fn name(self) -> &'static str {
+        match self {
+            CacheCategory::Search => "search",
+            CacheCategory::Detail => "detail",
+            CacheCategory::Douban => "douban",
+        }
+    }
+
+    /// Seconds a response is served without asking the network.
+    fn ttl_secs(self) -> u64 {
+        match self {
+            CacheCategory::Search => 10 * MINUTE,
+            CacheCategory::Detail => 6 * HOUR,
+            CacheCategory::Douban => 3 * DAY,
+        }
+    }
+
+    /// Seconds after the TTL during which the old response is still served while it is refreshed.
+    fn stale_secs(self) -> u64 {
+        match self {
+            CacheCategory::Search => HOUR,
+            CacheCategory::Detail => DAY,
+            CacheCategory::Douban => 7 * DAY,
+        }
+    }
+
+    fn freshness(self, age_secs: u64) -> Freshness {
+        if age_secs <= self.ttl_secs() {
+            Freshness::Fresh
+        } else if age_secs <= self.ttl_secs() + self.stale_secs() {
+            Freshness::Stale
+        } else {
+            Freshness::Expired
+        }
+    }
+}
+
+/// Where a response came from, reported in `HttpResponse.cache`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum CacheStatus {
+    /// Fresh response from the cache.
+    Hit,
+    /// Outdated response from the cache; a background request is refreshing it.
+    Stale,
+    /// Fetched from the network and stored.
+    Miss,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Freshness {
+    Fresh,
+    Stale,
+    Expired,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or_default()
+}
+
+// FNV-1a, stable across builds unlike `DefaultHasher`, so file names survive app updates
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// Cache key of a request: method, URL, the headers in `KEY_HEADERS` (hashed for `SECRET_HEADERS`) and
+/// how the body is decoded. `None` for requests that aren't cached (anything but a GET without body).
+pub(crate) fn request_key(options: &HttpRequestOptions) -> Option<String> {
+    let method = options.method.as_deref().unwrap_or("GET");
+    if !method.eq_ignore_ascii_case("GET") || options.body.is_some() {
+        return None;
+    }
+    let mut headers: Vec<String> = options
+        .headers
+        .iter()
+        .flatten()
+        .filter(|(name, _)| KEY_HEADERS.contains(&name.to_ascii_lowercase().as_str()))
+        .map(|(name, value)| {
+            let name = name.to_ascii_lowercase();
+            if SECRET_HEADERS.contains(&name.as_str()) {
+                format!("{}: sha256:{:x}", name, Sha256::digest(value.as_bytes()))
+            } else {
+                format!("{}: {}", name, value)
+            }
+        })
+        .collect();
+    headers.sort();
+    Some(format!(
+        "GET {}\n{}\nas_text: {}\ncharset: {}",
+        options.url,
+        headers.join("\n"),
+        options.response_as_text != Some(false),
+        options.charset.as_deref().unwrap_or_default()
+    ))
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredEntry {
+    key: String,
+    url: String,
+    category: CacheCategory,
+    stored_at: u64,
+    response: HttpResponse,
+}
+
+// Fields of a `StoredEntry` the index needs, read without the response
+#[derive(Deserialize)]
+struct StoredMeta {
+    url: String,
+    stored_at: u64,
+}
+
+// Everything `inspect_http_cache` and eviction need, so neither has to open the files
+struct IndexEntry {
+    category: CacheCategory,
+    url: String,
+    stored_at: u64,
+    size: u64,
+    last_used: u64,
+}
+
+/// One cached response, as listed by `inspect_http_cache`.
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct CacheEntryInfo {
+    url: String,
+    category: CacheCategory,
+    size_bytes: u64,
+    age_secs: u64,
+    freshness: Freshness,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct HttpCacheReport {
+    total_bytes: u64,
+    max_bytes: u64,
+    entries: Vec<CacheEntryInfo>,
+}
+
+/// Response cache in `dir`, shared through `HttpClient`.
+pub(crate) struct HttpCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    // File name -> size and use, so eviction doesn't have to scan the directory
+    index: Mutex<HashMap<String, IndexEntry>>,
+    // Keys with a background refresh in flight
+    revalidating: Mutex<HashSet<String>>,
+}
+
+impl HttpCache {
+    /// Opens the cache in `dir`, creating it if needed and indexing the files already there.
+    pub(crate) fn new(dir: PathBuf) -> Self {
+        if let Err(e) = fs::create_dir_all(&dir) {
+            log::warn!("[Rust] failed to create HTTP cache dir {}: {}", dir.display(), e);
+        }
+        let mut index = HashMap::new();
+        for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            // Skips leftover temp files of interrupted writes as well
+            let category = CacheCategory::ALL.into_iter().find(|category| file_name.starts_with(category.name()));
+            let (Some(category), true) = (category, file_name.ends_with(".json")) else {
+                continue;
+            };
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            let meta = fs::read_to_string(entry.path()).ok().and_then(|content| serde_json::from_str::<StoredMeta>(&content).ok());
+            let Some(StoredMeta { url, stored_at }) = meta else {
+                // Unreadable entries would never be served, only take up space
+                let _ = fs::remove_file(entry.path());
+                continue;
+            };
+            let modified = metadata.modified().ok().and_then(|time| time.duration_since(UNIX_EPOCH).ok());
+            let last_used = modified.map(|elapsed| elapsed.as_secs()).unwrap_or_default();
+            index.insert(file_name, IndexEntry { category, url, stored_at, size: metadata.len(), last_used });
+        }
+        HttpCache { dir, max_bytes: DEFAULT_MAX_BYTES, index: Mutex::new(index), revalidating: Mutex::default() }
+    }
+
+    fn file_name(key: &str, category: CacheCategory) -> String {
+        format!("{}-{:016x}.json", category.name(), fnv1a(key))
+    }
+
+    /// Cached response for `key` with its status, unless there is none or it has expired.
+    pub(crate) async fn lookup(self: &Arc<Self>, key: &str, category: CacheCategory) -> Option<(HttpResponse, CacheStatus)> {
+        let (cache, key) = (self.clone(), key.to_string());
+        match tokio::task::spawn_blocking(move || cache.lookup_at(&key, category, now_secs())).await {
+            Ok(cached) => cached,
+            Err(e) => {
+                log::warn!("[Rust] HTTP cache lookup failed: {}", e);
+                None
+            }
+        }
+    }
+
+    fn lookup_at(&self, key: &str, category: CacheCategory, now: u64) -> Option<(HttpResponse, CacheStatus)> {
+        let file_name = Self::file_name(key, category);
+        let content = fs::read_to_string(self.dir.join(&file_name)).ok()?;
+        let entry: StoredEntry = serde_json::from_str(&content).ok()?;
+        // Different request with the same hash
+        if entry.key != key {
+            return None;
+        }
+        let status = match category.freshness(now.saturating_sub(entry.stored_at)) {
+            Freshness::Fresh => CacheStatus::Hit,
+            Freshness::Stale => CacheStatus::Stale,
+            Freshness::Expired => return None,
+        };
+        if let Some(indexed) = self.index.lock().unwrap().get_mut(&file_name) {
+            indexed.last_used = now;
+        }
+        let mut response = entry.response;
+        response.cache = Some(status);
+        Some((response, status))
+    }
+
+    /// Stores a successful response in the background; failures to write are logged, the response
+    /// is still usable.
+    pub(crate) fn store(self: &Arc<Self>, key: &str, category: CacheCategory, url: &str, response: &HttpResponse) {
+        if !(200..300).contains(&response.status) {
+            return;
+        }
+        let (cache, key, url, response) = (self.clone(), key.to_string(), url.to_string(), response.clone());
+        tokio::task::spawn_blocking(move || cache.store_at(&key, category, &url, response, now_secs()));
+    }
+
+    fn store_at(&self, key: &str, category: CacheCategory, url: &str, response: HttpResponse, now: u64) {
+        if !(200..300).contains(&response.status) {
+            return;
+        }
+        let entry = StoredEntry { key: key.to_string(), url: url.to_string(), category, stored_at: now, response };
+        let json = match serde_json::to_string(&entry) {
+            Ok(json) => json,
+            Err(e) => return log::warn!("[Rust] failed to serialize cached response of {}: {}", url, e),
+        };
+        let file_name = Self::file_name(key, category);
+        let path = self.dir.join(&file_name);
+        // Write to a temp file first so readers never see a truncated entry
+        let tmp_path = path.with_extension("json.tmp");
+        if let Err(e) = fs::write(&tmp_path, &json).and_then(|_| fs::rename(&tmp_path, &path)) {
+            return log::warn!("[Rust] failed to write cache entry {}: {}", path.display(), e);
+        }
+        let mut index = self.index.lock().unwrap();
+        let indexed = IndexEntry { category, url: url.to_string(), stored_at: now, size: json.len() as u64, last_used: now };
+        index.insert(file_name, indexed);
+        self.evict(&mut index);
+    }
+
+    // Drops least recently used files until the cache fits into `max_bytes`
+    fn evict(&self, index: &mut HashMap<String, IndexEntry>) {
+        let mut total: u64 = index.values().map(|entry| entry.size).sum();
+        while total > self.max_bytes {
+            let Some(oldest) = index.iter().min_by_key(|(_, entry)| entry.last_used).map(|(name, _)| name.clone()) else {
+                break;
+            };
+            let entry = index.remove(&oldest).expect("oldest entry is indexed");
+            if let Err(e) = fs::remove_file(self.dir.join(&oldest)) {
+                log::warn!("[Rust] failed to evict cache entry {}: {}", oldest, e);
+            }
+            total -= entry.size;
+        }
+    }
+
+    /// Marks `key` as being refreshed; false if a refresh is already running.
+    pub(crate) fn begin_revalidation(&self, key: &str) -> bool {
+        self.revalidating.lock().unwrap().insert(key.to_string())
+    }
+
+    pub(crate) fn end_revalidation(&self, key: &str) {
+        self.revalidating.lock().unwrap().remove(key);
+    }
+
+    fn report(&self, category: Option<CacheCategory>) -> HttpCacheReport {
+        let now = now_secs();
+        let index = self.index.lock().unwrap();
+        let mut entries: Vec<CacheEntryInfo> = index
+            .values()
+            .filter(|indexed| category.map_or(true, |category| indexed.category == category))
+            .map(|indexed| {
+                let age_secs = now.saturating_sub(indexed.stored_at);
+                CacheEntryInfo {
+                    url: indexed.url.clone(),
+                    category: indexed.category,
+                    size_bytes: indexed.size,
+                    age_secs,
+                    freshness: indexed.category.freshness(age_secs),
+                }
+            })
+            .collect();
+        entries.sort_by_key(|entry| entry.age_secs);
+        HttpCacheReport { total_bytes: index.values().map(|entry| entry.size).sum(), max_bytes: self.max_bytes, entries }
+    }
+
+    fn clear(&self, category: Option<CacheCategory>) -> usize {
+        let mut index = self.index.lock().unwrap();
+        let doomed: Vec<String> = index
+            .iter()
+            .filter(|(_, indexed)| category.map_or(true, |category| indexed.category == category))
+            .map(|(file_name, _)| file_name.clone())
+            .collect();
+        for file_name in &doomed {
+            index.remove(file_name);
+            if let Err(e) = fs::remove_file(self.dir.join(file_name)) {
+                log::warn!("[Rust] failed to remove cache entry {}: {}", file_name, e);
+            }
+        }
+        doomed.len()
+    }
+}
+
+/// Details of the error the cache commands return when the cache dir couldn't be resolved at startup.
+const CACHE_UNAVAILABLE: &str = "the app cache directory could not be resolved";
+
+fn available_cache(http: &HttpClient) -> Result<Arc<HttpCache>, HttpError> {
+    http.cache().cloned().ok_or_else(|| {
+        HttpError::new(HttpErrorKind::InvalidConfig, "HTTP cache is not available", Some(CACHE_UNAVAILABLE.to_string()))
+    })
+}
+
+// Runs `work` on the blocking pool, off the main thread the sync commands would run on
+async fn run_blocking<T: Send + 'static>(work: impl FnOnce() -> T + Send + 'static) -> Result<T, HttpError> {
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|e| HttpError::new(HttpErrorKind::Other, "HTTP cache task failed", Some(e.to_string())))
+}
+
+/// Lists the cached responses, optionally of one category, newest first, with the cache's size.
+#[tauri::command]
+pub(crate) async fn inspect_http_cache(
+    category: Option<CacheCategory>,
+    http: tauri::State<'_, HttpClient>,
+) -> Result<HttpCacheReport, HttpError> {
+    let cache = available_cache(&http)?;
+    run_blocking(move || cache.report(category)).await
+}
+
+/// Removes the cached responses of `category`, or all of them, and returns how many were removed.
+#[tauri::command]
+pub(crate) async fn clear_http_cache(category: Option<CacheCategory>, http: tauri::State<'_, HttpClient>) -> Result<usize, HttpError> {
+    let cache = available_cache(&http)?;
+    let removed = run_blocking(move || cache.clear(category)).await?;
+    log::info!("[Rust] cleared {} HTTP cache entries", removed);
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BodyEncoding;
+
+    fn temp_cache(name: &str) -> HttpCache {
+        let dir = std::env::temp_dir().join(format!("libretv-http-cache-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        HttpCache::new(dir)
+    }
+
+    fn response(status: u16, body: &str) -> HttpResponse {
+        HttpResponse {
+            status,
+            headers: HashMap::new(),
+            body: body.to_string(),
+            body_encoding: BodyEncoding::Text,
+            content_type: Some("application/json".to_string()),
+            content_length: body.len(),
+            attempts: 1,
+            cache: None,
+        }
+    }
+
+    fn request(url: &str, headers: &[(&str, &str)]) -> HttpRequestOptions {
+        HttpRequestOptions {
+            url: url.to_string(),
+            headers: Some(headers.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn keys_on_method_url_and_relevant_headers() {
+        let plain = request_key(&request("https://a.example.com/api?wd=1", &[("User-Agent", "x")])).unwrap();
+        assert_eq!(plain, request_key(&request("https://a.example.com/api?wd=1", &[("User-Agent", "y")])).unwrap());
+        assert_ne!(plain, request_key(&request("https://a.example.com/api?wd=1", &[("Referer", "https://b/")])).unwrap());
+        assert_ne!(plain, request_key(&request("https://a.example.com/api?wd=2", &[])).unwrap());
+
+        // Credentials are part of the key, but never in plain text
+        let cookie = request_key(&request("https://a.example.com/api?wd=1", &[("Cookie", "session=s3cret")])).unwrap();
+        assert_ne!(plain, cookie);
+        assert!(!cookie.contains("s3cret"));
+        let authorization = request_key(&request("https://a.example.com/api?wd=1", &[("Authorization", "Bearer t0ken")])).unwrap();
+        assert!(authorization.starts_with("GET https://a.example.com/api?wd=1\nauthorization: sha256:"));
+        assert!(!authorization.contains("t0ken"));
+
+        let post = HttpRequestOptions { method: Some("POST".to_string()), ..request("https://a.example.com/api", &[]) };
+        assert!(request_key(&post).is_none());
+    }
+
+    #[test]
+    fn serves_fresh_then_stale_then_nothing() {
+        let cache = temp_cache("freshness");
+        let key = request_key(&request("https://a.example.com/api?wd=三体", &[])).unwrap();
+        cache.store_at(&key, CacheCategory::Search, "https://a.example.com/api?wd=三体", response(200, "{}"), 1_000);
+
+        let (cached, status) = cache.lookup_at(&key, CacheCategory::Search, 1_000 + 60).unwrap();
+        assert_eq!(status, CacheStatus::Hit);
+        assert_eq!(cached.body, "{}");
+        assert_eq!(cached.cache, Some(CacheStatus::Hit));
+        let (_, status) = cache.lookup_at(&key, CacheCategory::Search, 1_000 + 30 * MINUTE).unwrap();
+        assert_eq!(status, CacheStatus::Stale);
+        assert!(cache.lookup_at(&key, CacheCategory::Search, 1_000 + 2 * HOUR).is_none());
+        // Details live longer
+        cache.store_at(&key, CacheCategory::Detail, "https://a.example.com/api?wd=三体", response(200, "{}"), 1_000);
+        assert!(cache.lookup_at(&key, CacheCategory::Detail, 1_000 + 2 * HOUR).is_some());
+
+        // Errors aren't stored
+        let other = request_key(&request("https://a.example.com/missing", &[])).unwrap();
+        cache.store_at(&other, CacheCategory::Search, "https://a.example.com/missing", response(404, ""), 1_000);
+        assert!(cache.lookup_at(&other, CacheCategory::Search, 1_000).is_none());
+        assert_eq!(cache.clear(None), 2);
+    }
+
+    #[test]
+    fn evicts_least_recently_used_entries() {
+        let mut cache = temp_cache("eviction");
+        let body = "x".repeat(1000);
+        cache.max_bytes = 3000;
+        for (index, url) in ["https://a/1", "https://a/2", "https://a/3"].iter().enumerate() {
+            if index == 2 {
+                // Touch the first entry so the second one is the least recently used
+                cache.lookup_at(&request_key(&request("https://a/1", &[])).unwrap(), CacheCategory::Douban, 10);
+            }
+            cache.store_at(&request_key(&request(url, &[])).unwrap(), CacheCategory::Douban, url, response(200, &body), index as u64);
+        }
+        let report = cache.report(None);
+        let mut urls: Vec<&str> = report.entries.iter().map(|entry| entry.url.as_str()).collect();
+        urls.sort();
+        assert_eq!(urls, ["https://a/1", "https://a/3"]);
+        assert!(report.total_bytes <= 3000);
+
+        // The index is rebuilt from the files on the next start; broken files are dropped
+        fs::write(cache.dir.join("douban-broken.json"), "{").unwrap();
+        let reopened = HttpCache::new(cache.dir.clone());
+        let report = reopened.report(Some(CacheCategory::Douban));
+        let mut urls: Vec<&str> = report.entries.iter().map(|entry| entry.url.as_str()).collect();
+        urls.sort();
+        assert_eq!(urls, ["https://a/1", "https://a/3"]);
+        assert!(!cache.dir.join("douban-broken.json").exists());
+        assert_eq!(reopened.clear(Some(CacheCategory::Search)), 0);
+        assert_eq!(reopened.clear(Some(CacheCategory::Douban)), 2);
+    }
+}
@@ -0,0 +1,219 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::http_cache::HttpCache;
+use crate::proxy::{ProxyRoute, ProxyRouter};
+use crate::rate_limit::HostLimiter;
+use crate::{HttpError, HttpErrorKind};
+
+/// Timeout for requests that don't set `timeout_secs`.
+pub(crate) const DEFAULT_TIMEOUT_SECS: u64 = 20;
+
+/// Desktop browser User-Agent sent to collection sites and CDNs, same as API_CONFIG in JS.
+pub(crate) const BROWSER_USER_AGENT: &str =
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/122.0.0.0 Safari/537.36";
+
+/// Shared reqwest client, managed as Tauri state.
+///
+/// A single query fans out into dozens of requests against the same hosts; sharing one client keeps
+/// their connections, TLS sessions and keep-alive instead of paying the handshake on every invoke.
+/// Cloning is cheap, clones share the same pool, response cache, per-host limits and proxy.
+#[derive(Clone)]
+pub(crate) struct HttpClient {
+    clients: ClientPair,
+    cache: Option<Arc<HttpCache>>,
+    limiter: Arc<HostLimiter>,
+    proxies: Arc<ProxyRouter>,
+}
+
+/// Pool settings of the direct and the proxy client. Also used by `benches/http_client_pool.rs`.
+pub fn client_builder() -> reqwest::ClientBuilder {
+    reqwest::Client::builder()
+        .connect_timeout(Duration::from_secs(10))
+        .pool_idle_timeout(Duration::from_secs(90))
+        .pool_max_idle_per_host(8)
+        .tcp_keepalive(Duration::from_secs(60))
+    // No client-wide timeout: every request sets its own from HttpRequestOptions.timeout_secs
+}
+
+/// Two clients with the same settings: `follow` follows redirects, `manual` hands them back to the
+/// caller, for the `libretv://` protocol that checks every hop before requesting it.
+#[derive(Clone)]
+pub(crate) struct ClientPair {
+    follow: reqwest::Client,
+    manual: reqwest::Client,
+}
+
+impl ClientPair {
+    /// Builds both clients from `client_builder()` with `configure` applied, e.g. to add a proxy.
+    pub(crate) fn build(configure: impl Fn(reqwest::ClientBuilder) -> reqwest::ClientBuilder) -> reqwest::Result<Self> {
+        Ok(ClientPair {
+            follow: configure(client_builder()).build()?,
+            manual: configure(client_builder()).redirect(reqwest::redirect::Policy::none()).build()?,
+        })
+    }
+}
+
+/// Host of `url` with the port if it isn't the scheme's default, e.g. `example.com:8080`.
+pub(crate) fn host_key(url: &str) -> Option<String> {
+    let url = reqwest::Url::parse(url).ok()?;
+    let host = url.host_str()?;
+    Some(match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    })
+}
+
+impl HttpClient {
+    pub(crate) fn new() -> Result<Self, HttpError> {
+        let clients = ClientPair::build(|builder| builder)
+            .map_err(|e| HttpError::new(HttpErrorKind::Other, "Failed to build HTTP client", Some(e.to_string())))?;
+        Ok(HttpClient { clients, cache: None, limiter: Arc::default(), proxies: Arc::default() })
+    }
+
+    /// Lets requests with `HttpRequestOptions.cache` use `cache`.
+    pub(crate) fn with_cache(mut self, cache: HttpCache) -> Self {
+        self.cache = Some(Arc::new(cache));
+        self
+    }
+
+    /// Client for a request to `url`: the proxy client when the proxy is set and `url`'s route is
+    /// `Proxy`, see `ProxyRouter::clients_for`.
+    pub(crate) fn client(&self, url: &str, route: Option<ProxyRoute>) -> reqwest::Client {
+        self.clients_for(url, route).follow
+    }
+
+    /// Like `client`, but redirects come back as responses instead of being followed.
+    pub(crate) fn client_without_redirects(&self, url: &str, route: Option<ProxyRoute>) -> reqwest::Client {
+        self.clients_for(url, route).manual
+    }
+
+    fn clients_for(&self, url: &str, route: Option<ProxyRoute>) -> ClientPair {
+        self.proxies.clients_for(url, route).unwrap_or_else(|| self.clients.clone())
+    }
+
+    pub(crate) fn cache(&self) -> Option<&Arc<HttpCache>> {
+        self.cache.as_ref()
+    }
+
+    pub(crate) fn limiter(&self) -> &HostLimiter {
+        &self.limiter
+    }
+
+    pub(crate) fn proxies(&self) -> &ProxyRouter {
+        &self.proxies
+    }
+}
+
+// --- Retry ---
+
+/// Error classes a `RetryPolicy` can retry.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum RetryableError {
+    Timeout,
+    Connect,
+    ServerError, // HTTP 5xx
+}
+
+/// Upper bound for `RetryPolicy.max_attempts`, so a request can't keep a source busy for minutes.
+pub(crate) const MAX_RETRY_ATTEMPTS: u32 = 5;
+
+/// Opt-in retry with exponential backoff, set through `HttpRequestOptions.retry`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub(crate) struct RetryPolicy {
+    /// Total tries including the first one, 1 to `MAX_RETRY_ATTEMPTS`.
+    pub(crate) max_attempts: u32,
+    /// Delay before the second try; doubled for every further one.
+    pub(crate) initial_backoff_ms: u64,
+    pub(crate) max_backoff_ms: u64,
+    pub(crate) retry_on: Vec<RetryableError>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff_ms: 300,
+            max_backoff_ms: 5000,
+            retry_on: vec![RetryableError::Timeout, RetryableError::Connect, RetryableError::ServerError],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Rejects a policy without any try and caps `max_attempts` at `MAX_RETRY_ATTEMPTS`.
+    pub(crate) fn validated(self) -> Result<Self, HttpError> {
+        if self.max_attempts == 0 {
+            return Err(HttpError::new(HttpErrorKind::InvalidRequest, "retry.max_attempts must be at least 1", None));
+        }
+        Ok(RetryPolicy { max_attempts: self.max_attempts.min(MAX_RETRY_ATTEMPTS), ..self })
+    }
+
+    pub(crate) fn should_retry(&self, class: Option<RetryableError>, attempt: u32) -> bool {
+        attempt < self.max_attempts && class.is_some_and(|class| self.retry_on.contains(&class))
+    }
+
+    /// Backoff after the failed try number `attempt` (1-based).
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u64 << attempt.saturating_sub(1).min(16);
+        Duration::from_millis(self.initial_backoff_ms.saturating_mul(factor).min(self.max_backoff_ms))
+    }
+}
+
+impl RetryableError {
+    pub(crate) fn from_kind(kind: HttpErrorKind) -> Option<Self> {
+        match kind {
+            HttpErrorKind::Timeout => Some(RetryableError::Timeout),
+            HttpErrorKind::Connect => Some(RetryableError::Connect),
+            HttpErrorKind::Status(status) if status >= 500 => Some(RetryableError::ServerError),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_max_attempts() {
+        let policy = |max_attempts| RetryPolicy { max_attempts, ..Default::default() };
+        let error = policy(0).validated().unwrap_err();
+        assert_eq!(error.kind, HttpErrorKind::InvalidRequest);
+        assert_eq!(policy(1).validated().unwrap().max_attempts, 1);
+        assert_eq!(policy(3).validated().unwrap().max_attempts, 3);
+        assert_eq!(policy(1000).validated().unwrap().max_attempts, MAX_RETRY_ATTEMPTS);
+    }
+
+    #[test]
+    fn retries_only_listed_classes_while_attempts_remain() {
+        let policy = RetryPolicy { retry_on: vec![RetryableError::Timeout, RetryableError::ServerError], ..Default::default() };
+        assert!(policy.should_retry(Some(RetryableError::Timeout), 1));
+        assert!(policy.should_retry(Some(RetryableError::ServerError), 2));
+        assert!(!policy.should_retry(Some(RetryableError::Timeout), 3));
+        assert!(!policy.should_retry(Some(RetryableError::Connect), 1));
+        assert!(!policy.should_retry(None, 1));
+        let single = RetryPolicy { max_attempts: 1, ..Default::default() };
+        assert!(!single.should_retry(Some(RetryableError::Timeout), 1));
+    }
+
+    #[test]
+    fn classifies_error_kinds() {
+        assert_eq!(RetryableError::from_kind(HttpErrorKind::Timeout), Some(RetryableError::Timeout));
+        assert_eq!(RetryableError::from_kind(HttpErrorKind::Connect), Some(RetryableError::Connect));
+        assert_eq!(RetryableError::from_kind(HttpErrorKind::Status(503)), Some(RetryableError::ServerError));
+        assert_eq!(RetryableError::from_kind(HttpErrorKind::Status(404)), None);
+        assert_eq!(RetryableError::from_kind(HttpErrorKind::Decode), None);
+    }
+
+    #[test]
+    fn doubles_backoff_up_to_the_cap() {
+        let policy = RetryPolicy { initial_backoff_ms: 300, max_backoff_ms: 1000, ..Default::default() };
+        let backoffs: Vec<_> = (1..=4).map(|attempt| policy.backoff(attempt).as_millis()).collect();
+        assert_eq!(backoffs, [300, 600, 1000, 1000]);
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_millis(1000));
+    }
+}
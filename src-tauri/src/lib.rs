@@ -1,322 +1,243 @@
 use reqwest::header::{HeaderMap, HeaderValue, USER_AGENT, CONTENT_TYPE};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use tauri;
+use tauri::Manager;
 use once_cell::sync::Lazy; // For lazy static initialization
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+
+mod ad_filter;
+mod cancel;
+mod charset;
+mod hls_proxy;
+mod html_detail;
+mod http_cache;
+mod http_client;
+mod m3u8;
+mod merge;
+mod probe;
+mod proxy;
+mod ranking;
+mod rate_limit;
+mod search;
+mod sources;
+mod stream_probe;
+mod vod;
+
+// The benches measure the client settings the app actually uses
+pub use http_client::client_builder;
+
+use cancel::RequestGroups;
+use http_cache::{CacheCategory, CacheStatus, HttpCache};
+use http_client::{HttpClient, RetryPolicy, RetryableError};
+use proxy::ProxyRoute;
+use rate_limit::HostLimits;
+use sources::SourceRegistry;
+use sources::{ApiSourceInfo, ApiType};
+use vod::{Episode, PlayLine, SearchResultItem, VideoDetail, VideoInfo};
 
-// --- API Configuration Structures ---
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-enum ApiType {
-    Json,
-    Html, // For sources where details are scraped from HTML
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct ApiSourceInfo {
-    api_base_url: String,
-    name: String,
-    detail_base_url: Option<String>, // For HTML detail pages or different detail API base
-    api_type: ApiType, // To distinguish between JSON API and HTML scraping for details
-    search_path: Option<String>, // Specific search path if different from default
-    detail_path: Option<String>, // Specific detail path if different from default (for JSON APIs)
-    // Example: some sources might use /vodsearch instead of /api.php/provide/vod/...
-}
-
-// Using a more specific name for the map key if needed, e.g. SourceId(String)
-static API_SITES_CONFIG: Lazy<HashMap<String, ApiSourceInfo>> = Lazy::new(|| {
-    let mut m = HashMap::new();
-    // Data from public/js/config.js API_SITES, adapted for Rust
-    // Note: Detail paths for HTML sources are usually part of detail_base_url construction
-    // Search paths and detail paths for JSON sources can use defaults or be overridden here
-    
-    // Example entry (need to populate all from config.js)
-    m.insert("dyttzy".to_string(), ApiSourceInfo {
-        api_base_url: "http://caiji.dyttzyapi.com".to_string(),
-        name: "电影天堂资源".to_string(),
-        detail_base_url: Some("http://caiji.dyttzyapi.com".to_string()), // Assuming it's JSON, or specific HTML base
-        api_type: ApiType::Json, // Assuming JSON, adjust if it's HTML scraping for detail
-        search_path: None, // Uses default
-        detail_path: None, // Uses default
-    });
-    m.insert("ruyi".to_string(), ApiSourceInfo {
-        api_base_url: "https://cj.rycjapi.com".to_string(),
-        name: "如意资源".to_string(),
-        detail_base_url: None,
-        api_type: ApiType::Json,
-        search_path: None, detail_path: None,
-    });
-    m.insert("bfzy".to_string(), ApiSourceInfo {
-        api_base_url: "https://bfzyapi.com".to_string(),
-        name: "暴风资源".to_string(),
-        detail_base_url: None,
-        api_type: ApiType::Json,
-        search_path: None, detail_path: None,
-    });
-    m.insert("tyyszy".to_string(), ApiSourceInfo {
-        api_base_url: "https://tyyszy.com".to_string(),
-        name: "天涯资源".to_string(),
-        detail_base_url: None,
-        api_type: ApiType::Json,
-        search_path: None, detail_path: None,
-    });
-    m.insert("xiaomaomi".to_string(), ApiSourceInfo {
-        api_base_url: "https://zy.xiaomaomi.cc".to_string(),
-        name: "小猫咪资源".to_string(),
-        detail_base_url: None,
-        api_type: ApiType::Json,
-        search_path: None, detail_path: None,
-    });
-    m.insert("ffzy".to_string(), ApiSourceInfo { // ffzy has HTML detail
-        api_base_url: "http://ffzy5.tv".to_string(),
-        name: "非凡影视".to_string(),
-        detail_base_url: Some("http://ffzy5.tv".to_string()), // Base for HTML detail
-        api_type: ApiType::Html, // Detail is HTML
-        search_path: None, // JSON search
-        detail_path: Some("/index.php/vod/detail/id/{id}.html".to_string()), // Path template for HTML detail
-    });
-    m.insert("heimuer".to_string(), ApiSourceInfo { // heimuer has HTML detail
-        api_base_url: "https://json.heimuer.xyz".to_string(),
-        name: "黑木耳".to_string(),
-        detail_base_url: Some("https://heimuer.tv".to_string()), // Base for HTML detail
-        api_type: ApiType::Html, // Detail is HTML
-        search_path: None, // JSON search
-        detail_path: Some("/index.php/vod/detail/id/{id}.html".to_string()), // Path template for HTML detail
-    });
-    m.insert("zy360".to_string(), ApiSourceInfo {
-        api_base_url: "https://360zy.com".to_string(),
-        name: "360资源".to_string(),
-        detail_base_url: None,
-        api_type: ApiType::Json,
-        search_path: None, detail_path: None,
-    });
-    m.insert("wolong".to_string(), ApiSourceInfo {
-        api_base_url: "https://wolongzyw.com".to_string(),
-        name: "卧龙资源".to_string(),
-        detail_base_url: None,
-        api_type: ApiType::Json,
-        search_path: None, detail_path: None,
-    });
-    m.insert("hwba".to_string(), ApiSourceInfo {
-        api_base_url: "https://cjhwba.com".to_string(),
-        name: "华为吧资源".to_string(),
-        detail_base_url: None,
-        api_type: ApiType::Json,
-        search_path: None, detail_path: None,
-    });
-    m.insert("jisu".to_string(), ApiSourceInfo {
-        api_base_url: "https://jszyapi.com".to_string(),
-        name: "极速资源".to_string(),
-        detail_base_url: Some("https://jszyapi.com".to_string()),
-        api_type: ApiType::Json, // Assuming JSON, adjust if HTML
-        search_path: None, detail_path: None,
-    });
-    m.insert("dbzy".to_string(), ApiSourceInfo {
-        api_base_url: "https://dbzy.com".to_string(),
-        name: "豆瓣资源".to_string(),
-        detail_base_url: None,
-        api_type: ApiType::Json,
-        search_path: None, detail_path: None,
-    });
-    m.insert("mozhua".to_string(), ApiSourceInfo {
-        api_base_url: "https://mozhuazy.com".to_string(),
-        name: "魔爪资源".to_string(),
-        detail_base_url: None,
-        api_type: ApiType::Json,
-        search_path: None, detail_path: None,
-    });
-    m.insert("mdzy".to_string(), ApiSourceInfo {
-        api_base_url: "https://www.mdzyapi.com".to_string(),
-        name: "魔都资源".to_string(),
-        detail_base_url: None,
-        api_type: ApiType::Json,
-        search_path: None, detail_path: None,
-    });
-    m.insert("zuid".to_string(), ApiSourceInfo {
-        api_base_url: "https://api.zuidapi.com".to_string(),
-        name: "最大资源".to_string(),
-        detail_base_url: None,
-        api_type: ApiType::Json,
-        search_path: None, detail_path: None,
-    });
-    m.insert("yinghua".to_string(), ApiSourceInfo {
-        api_base_url: "https://m3u8.apiyhzy.com".to_string(),
-        name: "樱花资源".to_string(),
-        detail_base_url: None,
-        api_type: ApiType::Json,
-        search_path: None, detail_path: None,
-    });
-    m.insert("baidu".to_string(), ApiSourceInfo {
-        api_base_url: "https://api.apibdzy.com".to_string(),
-        name: "百度云资源".to_string(),
-        detail_base_url: None,
-        api_type: ApiType::Json,
-        search_path: None, detail_path: None,
-    });
-    m.insert("wujin".to_string(), ApiSourceInfo {
-        api_base_url: "https://api.wujinapi.me".to_string(),
-        name: "无尽资源".to_string(),
-        detail_base_url: None,
-        api_type: ApiType::Json,
-        search_path: None, detail_path: None,
-    });
-    m.insert("wwzy".to_string(), ApiSourceInfo {
-        api_base_url: "https://wwzy.tv".to_string(),
-        name: "旺旺短剧".to_string(),
-        detail_base_url: None, // Assuming JSON, adjust if HTML
-        api_type: ApiType::Json,
-        search_path: None, detail_path: None,
-    });
-    m.insert("ikun".to_string(), ApiSourceInfo {
-        api_base_url: "https://ikunzyapi.com".to_string(),
-        name: "iKun资源".to_string(),
-        detail_base_url: None,
-        api_type: ApiType::Json,
-        search_path: None, detail_path: None,
-    });
-    // Add other sources from config.js here...
-    m
-});
 
 struct ApiPathConfig {
     search: String,
-    // detail_json: String, // For JSON detail APIs // Commented out to avoid dead_code warning for now
+    search_page: String, // Paged search with {query}/{page} placeholders
+    max_search_pages: u32, // Upper bound for "all pages" searches
+    detail_json: String, // For JSON detail APIs
     // HTML detail paths are part of ApiSourceInfo.detail_path_template
 }
 
 static API_PATH_DEFAULTS: Lazy<ApiPathConfig> = Lazy::new(|| ApiPathConfig {
     search: "/api.php/provide/vod/?ac=videolist&wd=".to_string(),
-    // detail_json: "/api.php/provide/vod/?ac=videolist&ids=".to_string(), // Commented out
+    search_page: "/api.php/provide/vod/?ac=videolist&wd={query}&pg={page}".to_string(),
+    max_search_pages: 50, // Same as API_CONFIG.search.maxPages in JS
+    detail_json: "/api.php/provide/vod/?ac=videolist&ids=".to_string(),
 });
 
 // --- End API Configuration Structures ---
 
 // --- New Tauri Commands for API access ---
 
-#[derive(Serialize, Deserialize, Debug)]
-struct SearchResultItem {
-    // Define fields based on what the JS expects in the 'list' array
-    // e.g., vod_id, vod_name, vod_pic, type_name, vod_remarks, etc.
-    // For now, we'll assume the body from make_http_request is a JSON string
-    // that the frontend can parse directly. If we need to process it in Rust,
-    // we'd define more specific structs here.
-    // This command will return the raw JSON string body for now.
-}
-
+/// Normalized search result returned to the frontend; items are already tagged with their source.
 #[derive(Serialize, Deserialize, Debug)]
 struct SearchResponse {
     code: u16,
     msg: Option<String>,
-    list: serde_json::Value, // Assuming list can be any array of objects
-                             // Or define a Vec<SearchResultItem> if item structure is fixed
+    list: Vec<SearchResultItem>,
+    page: u32, // Requested page, 1 when all pages were fetched
+    pagecount: Option<i64>, // As reported by the source
+    pages_fetched: u32,
 }
 
 
+/// Searches one source. `options.page` selects a single page (default 1); with `options.all_pages` the page
+/// count of the first response is followed and up to `max_pages` pages are fetched concurrently and combined.
+/// With `options.group_id` the search can be aborted by `cancel_requests`.
 #[tauri::command]
 async fn search_videos(
     query: String,
     source_id: String,
     custom_api_url: Option<String>,
-) -> Result<String, HttpError> {
-    let source_info: ApiSourceInfo;
-    let base_url: String;
-
-    if source_id == "custom" {
-        match custom_api_url {
-            Some(url) => {
-                base_url = url;
-                // For custom, we might not have a full ApiSourceInfo,
-                // or we create a temporary one.
-                // For now, assume custom sources use default paths.
-                source_info = ApiSourceInfo {
-                    api_base_url: base_url.clone(),
-                    name: "Custom".to_string(),
-                    detail_base_url: None,
-                    api_type: ApiType::Json, // Assume custom is JSON for search
-                    search_path: None,
-                    detail_path: None,
-                };
-            }
-            None => {
-                return Err(HttpError {
-                    error: "Custom source selected but no API URL provided".to_string(),
-                    details: None,
-                });
-            }
-        }
-    } else {
-        match API_SITES_CONFIG.get(&source_id) {
-            Some(info) => {
-                source_info = info.clone();
-                base_url = source_info.api_base_url.clone();
-            }
-            None => {
-                return Err(HttpError {
-                    error: format!("Unknown source_id: {}", source_id),
-                    details: None,
-                });
-            }
-        }
+    options: Option<search::SearchOptions>,
+    registry: tauri::State<'_, SourceRegistry>,
+    http: tauri::State<'_, HttpClient>,
+    groups: tauri::State<'_, RequestGroups>,
+) -> Result<SearchResponse, HttpError> {
+    let source_info = registry.resolve(&source_id, custom_api_url.clone())?;
+    let options = options.unwrap_or_default();
+    let pages = search::PageSelection::from_options(&options);
+    let response = {
+        let (http, source_info, query) = (HttpClient::clone(&http), source_info.clone(), query.clone());
+        groups.run(options.group_id.clone(), async move { search::fetch_search_pages(&http, &source_info, &query, 20, pages).await }).await?
+    };
+
+    // Same display name as handleSingleSourceSearch used to build in JS
+    let source_name = match &custom_api_url {
+        Some(url) if source_id == "custom" => reqwest::Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(|host| format!("自定义 ({})", host)))
+            .unwrap_or_else(|| "自定义源".to_string()),
+        _ => source_info.name.clone(),
+    };
+    let api_url = custom_api_url.as_deref().filter(|_| source_id == "custom");
+
+    let mut list = response.list;
+    for item in &mut list {
+        item.tag_source(&source_id, &source_name, api_url);
     }
+    ranking::rank_results(&mut list, &query, options.year, |_| None);
+    Ok(SearchResponse {
+        code: 200,
+        msg: response.msg,
+        list,
+        page: pages.first_page(),
+        pagecount: response.pagecount,
+        pages_fetched: response.pages_fetched,
+    })
+}
 
-    let search_path_template = source_info.search_path.as_deref().unwrap_or(&API_PATH_DEFAULTS.search);
-    // Ensure query is URL encoded
-    let encoded_query = urlencoding::encode(&query);
-    let full_url = format!("{}{}{}", base_url, search_path_template, encoded_query);
 
-    // Prepare headers - use defaults from API_CONFIG in JS for now
-    // Later, API_CONFIG.search.headers can also be moved to Rust
+#[tauri::command]
+async fn get_video_detail(
+    source_id: String,
+    vod_id: String,
+    custom_api_url: Option<String>,
+    registry: tauri::State<'_, SourceRegistry>,
+    http: tauri::State<'_, HttpClient>,
+) -> Result<VideoDetail, HttpError> {
+    if vod_id.is_empty() || !vod_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        return Err(HttpError::new(HttpErrorKind::InvalidRequest, "Invalid video id", Some(vod_id)));
+    }
+    let source_info = registry.resolve(&source_id, custom_api_url)?;
+    if source_info.api_type == ApiType::Html {
+        return get_html_video_detail(&http, &source_id, &vod_id, &source_info).await;
+    }
+
+    let detail_path = source_info.detail_path.as_deref().unwrap_or(&API_PATH_DEFAULTS.detail_json);
+    let detail_url = format!("{}{}{}", source_info.api_base_url, detail_path, vod_id);
+
     let mut headers = HashMap::new();
-    headers.insert("User-Agent".to_string(), "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/122.0.0.0 Safari/537.36".to_string());
+    headers.insert("User-Agent".to_string(), http_client::BROWSER_USER_AGENT.to_string());
     headers.insert("Accept".to_string(), "application/json".to_string());
-    
-    let http_options = HttpRequestOptions {
-        url: full_url,
+    let http_response = send_validated_request(&http, HttpRequestOptions {
+        url: detail_url.clone(),
         method: Some("GET".to_string()),
         headers: Some(headers),
         body: None,
-        timeout_secs: Some(20), // Default timeout
-        response_as_text: Some(true), // Get raw body to pass to JS
-    };
+        timeout_secs: Some(20),
+        response_as_text: Some(true),
+        retry: None,
+        charset: source_info.charset.clone(),
+        cache: Some(CacheCategory::Detail),
+        rate_limit: source_info.rate_limit,
+        proxy: source_info.proxy,
+    }, vod::is_detail_response)
+    .await?;
+    if !(200..300).contains(&http_response.status) {
+        return Err(HttpError::new(
+            HttpErrorKind::Status(http_response.status),
+            format!("API request failed with status: {}", http_response.status),
+            Some(http_response.body),
+        ));
+    }
 
-    match make_http_request(http_options).await {
-        Ok(http_response) => {
-            if http_response.status >= 200 && http_response.status < 300 {
-                // The JS side expects a JSON string that it will parse.
-                // The body from make_http_request is already a String.
-                // We need to ensure this string is a valid JSON that the frontend expects.
-                // For now, we directly return the body.
-                // The JS `handleSingleSourceSearch` adds source_name and source_code to each item.
-                // This logic might need to be replicated here if we return a parsed struct,
-                // or the JS can continue doing that after receiving the list.
-                // To match the JS function's return { code: 200, list: ... },
-                // we might need to parse here and re-serialize, or adjust JS.
-                // For now, let's assume the body is the JSON string the JS handler would have produced
-                // for the "list" part, and JS will wrap it.
-                // OR, more simply, return the raw body and let JS parse it.
-                // The original JS `handleSingleSourceSearch` returns JSON.stringify({ code: 200, list: responseData.list || [] });
-                // So, if make_http_request's body is the direct API JSON, we might need to parse it,
-                // extract the list, and then re-wrap.
-
-                // Directly return the body string as per our design.
-                // JS side (handleSingleSourceSearch) will parse this JSON string.
-                Ok(http_response.body)
-            } else {
-                Err(HttpError {
-                    error: format!("API request failed with status: {}", http_response.status),
-                    details: Some(http_response.body),
-                })
-            }
-        }
-        Err(e) => Err(e), // Propagate error from make_http_request
+    let source_name = if source_id == "custom" { "自定义源".to_string() } else { source_info.name.clone() };
+    vod::parse_video_detail(&http_response.body, &source_id, &source_name, detail_url)
+}
+
+
+// Detail for ApiType::Html sources: the page at detail_base_url + detail_path is scraped
+// with the source's html_rules instead of calling the JSON API.
+async fn get_html_video_detail(
+    http: &HttpClient,
+    source_id: &str,
+    vod_id: &str,
+    source_info: &ApiSourceInfo,
+) -> Result<VideoDetail, HttpError> {
+    let detail_base_url = source_info.detail_base_url.as_deref().unwrap_or(&source_info.api_base_url);
+    let detail_path = source_info.detail_path.as_deref().unwrap_or("/index.php/vod/detail/id/{id}.html");
+    let detail_url = format!("{}{}", detail_base_url, detail_path.replace("{id}", vod_id));
+
+    let mut headers = HashMap::new();
+    headers.insert("User-Agent".to_string(), http_client::BROWSER_USER_AGENT.to_string());
+    let http_response = send_http_request(http, HttpRequestOptions {
+        url: detail_url.clone(),
+        method: Some("GET".to_string()),
+        headers: Some(headers),
+        body: None,
+        timeout_secs: Some(15),
+        response_as_text: Some(true),
+        retry: None,
+        charset: source_info.charset.clone(),
+        cache: Some(CacheCategory::Detail),
+        rate_limit: source_info.rate_limit,
+        proxy: source_info.proxy,
+    })
+    .await?;
+    if !(200..300).contains(&http_response.status) {
+        return Err(HttpError::new(
+            HttpErrorKind::Status(http_response.status),
+            format!("Detail page request failed with status: {}", http_response.status),
+            Some(http_response.body.chars().take(200).collect()),
+        ));
     }
+
+    let rules = source_info.html_rules.clone().unwrap_or_default();
+    let detail = html_detail::extract_html_detail(&http_response.body, &rules, Some(&detail_url))?;
+
+    // Scraped pages don't name their lines, so everything found goes into a single one
+    let play_lines = if detail.episodes.is_empty() {
+        Vec::new()
+    } else {
+        vec![PlayLine {
+            name: source_info.name.clone(),
+            episodes: detail
+                .episodes
+                .iter()
+                .enumerate()
+                .map(|(index, url)| Episode { label: format!("第{}集", index + 1), url: url.clone() })
+                .collect(),
+        }]
+    };
+
+    Ok(VideoDetail {
+        code: 200,
+        episodes: detail.episodes,
+        play_lines,
+        detail_url,
+        video_info: VideoInfo {
+            title: detail.title,
+            cover: detail.cover,
+            desc: detail.desc,
+            source_name: source_info.name.clone(),
+            source_code: source_id.to_string(),
+            ..Default::default()
+        },
+    })
 }
 
 
 // --- End New Tauri Commands ---
 
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct HttpRequestOptions {
     url: String,
     method: Option<String>,
@@ -324,23 +245,151 @@ pub struct HttpRequestOptions {
     body: Option<serde_json::Value>,
     timeout_secs: Option<u64>,
     response_as_text: Option<bool>, // New field
+    retry: Option<RetryPolicy>, // Opt-in retries with exponential backoff, at most 5 tries
+    charset: Option<String>, // Decode text bodies with this encoding instead of detecting it, e.g. "gbk"
+    cache: Option<CacheCategory>, // Serve GET responses from the on-disk cache with this category's lifetime
+    rate_limit: Option<HostLimits>, // Limits for this request on top of its host's (see rate_limit.rs)
+    proxy: Option<ProxyRoute>, // Direct or through the configured proxy, instead of the host's route (see proxy.rs)
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// How `HttpResponse.body` is encoded.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BodyEncoding {
+    Text,
+    Base64, // Raw bytes, for `response_as_text: false`
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct HttpResponse {
     status: u16,
     headers: HashMap<String, String>,
     body: String,
+    body_encoding: BodyEncoding,
+    content_type: Option<String>,
+    content_length: usize, // Size of the body in bytes, before any base64 encoding
+    attempts: u32, // Number of tries it took, 1 without retries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cache: Option<CacheStatus>, // Only set for requests with `cache`
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Machine-readable category of an `HttpError`, so the frontend can pick the matching
+/// `ERROR_MESSAGES` entry instead of parsing `error` strings. Serialized as `{"code": "timeout"}`,
+/// or `{"code": "status", "status": 404}` for HTTP status failures.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "code", content = "status", rename_all = "snake_case")]
+pub enum HttpErrorKind {
+    Timeout,
+    Connect,
+    /// Upstream answered with a non-success HTTP status.
+    Status(u16),
+    /// Body couldn't be read or parsed (invalid JSON, broken stream…).
+    Decode,
+    /// The API answered, but with an error code or without the expected data.
+    Api,
+    InvalidRequest,
+    InvalidConfig,
+    UnknownSource,
+    Cancelled,
+    /// Local file access, e.g. the source registry.
+    Io,
+    Other,
+}
+
+impl HttpErrorKind {
+    pub(crate) fn from_reqwest(e: &reqwest::Error) -> Self {
+        if e.is_timeout() {
+            HttpErrorKind::Timeout
+        } else if e.is_connect() {
+            HttpErrorKind::Connect
+        } else if e.is_builder() {
+            HttpErrorKind::InvalidRequest
+        } else if let Some(status) = e.status() {
+            HttpErrorKind::Status(status.as_u16())
+        } else if e.is_body() || e.is_decode() {
+            HttpErrorKind::Decode
+        } else {
+            HttpErrorKind::Other
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct HttpError {
     error: String,
     details: Option<String>,
+    kind: HttpErrorKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    attempts: Option<u32>, // Set by send_http_request: how many tries were made before giving up
 }
 
+impl HttpError {
+    pub(crate) fn new(kind: HttpErrorKind, error: impl Into<String>, details: Option<String>) -> Self {
+        HttpError { error: error.into(), details, kind, attempts: None }
+    }
+}
+
+/// With a `group_id` the request can be aborted by `cancel_requests`.
 #[tauri::command]
-async fn make_http_request(options: HttpRequestOptions) -> Result<HttpResponse, HttpError> { // Removed pub
+async fn make_http_request(
+    options: HttpRequestOptions,
+    group_id: Option<String>,
+    http: tauri::State<'_, HttpClient>,
+    groups: tauri::State<'_, RequestGroups>,
+) -> Result<HttpResponse, HttpError> { // Removed pub
+    let http = HttpClient::clone(&http);
+    groups.run(group_id, async move { send_http_request(&http, options).await }).await
+}
+
+/// Performs a request with the shared client; used by `make_http_request` and the other commands.
+/// With `options.cache` set, GET responses come from the on-disk cache while usable; stale ones are
+/// returned right away and refreshed in the background.
+pub(crate) async fn send_http_request(http: &HttpClient, options: HttpRequestOptions) -> Result<HttpResponse, HttpError> {
+    send_validated_request(http, options, |_| true).await
+}
+
+/// `send_http_request` that only caches responses `cacheable` accepts, so API errors answered with
+/// HTTP 200 (e.g. a MacCMS `code` other than success) aren't served from the cache afterwards.
+pub(crate) async fn send_validated_request(
+    http: &HttpClient,
+    options: HttpRequestOptions,
+    cacheable: fn(&HttpResponse) -> bool,
+) -> Result<HttpResponse, HttpError> {
+    let cached = http.cache().zip(options.cache);
+    let Some(((cache, category), key)) = cached.zip(http_cache::request_key(&options)) else {
+        return fetch_with_retry(http, options).await;
+    };
+    match cache.lookup(&key, category).await {
+        Some((response, CacheStatus::Hit)) => return Ok(response),
+        Some((response, _)) => {
+            if cache.begin_revalidation(&key) {
+                let (http, cache) = (http.clone(), cache.clone());
+                tokio::spawn(async move {
+                    let url = options.url.clone();
+                    match fetch_with_retry(&http, options).await {
+                        Ok(fresh) if cacheable(&fresh) => cache.store(&key, category, &url, &fresh),
+                        Ok(_) => log::warn!("[Rust] refreshing cached {} returned an unusable response", url),
+                        Err(e) => log::warn!("[Rust] refreshing cached {} failed: {}", url, e.error),
+                    }
+                    cache.end_revalidation(&key);
+                });
+            }
+            return Ok(response);
+        }
+        None => {}
+    }
+    let url = options.url.clone();
+    let mut response = fetch_with_retry(http, options).await?;
+    if cacheable(&response) {
+        cache.store(&key, category, &url, &response);
+    }
+    response.cache = Some(CacheStatus::Miss);
+    Ok(response)
+}
+
+/// Sends the request; with `options.retry` set, retryable failures are tried again after an
+/// exponential backoff.
+async fn fetch_with_retry(http: &HttpClient, options: HttpRequestOptions) -> Result<HttpResponse, HttpError> {
     log::debug!("[Rust] make_http_request called with URL: {}, Method: {:?}, Headers: {:?}, Timeout: {:?}", 
         options.url, 
         options.method, 
@@ -348,13 +397,52 @@ async fn make_http_request(options: HttpRequestOptions) -> Result<HttpResponse,
         options.timeout_secs
     );
 
-    let client_builder = reqwest::Client::builder();
-    
-    let timeout_duration = std::time::Duration::from_secs(options.timeout_secs.unwrap_or(20));
-    let client = client_builder
-        .timeout(timeout_duration)
-        .build()
-        .map_err(|e| HttpError { error: "Failed to build HTTP client".to_string(), details: Some(e.to_string()) })?;
+    let url = options.url.clone();
+    // Text unless the caller explicitly asks for bytes
+    let encoding = if options.response_as_text == Some(false) { BodyEncoding::Base64 } else { BodyEncoding::Text };
+    let charset = options.charset.clone();
+    let retry = match options.retry.clone() {
+        Some(retry) => retry.validated()?,
+        None => RetryPolicy { max_attempts: 1, ..Default::default() },
+    };
+    let rate_limit = options.rate_limit;
+    // Picked once, so all tries of a request go the same way even if the proxy changes meanwhile
+    let client = http.client(&url, options.proxy);
+    let request = build_request(&client, options)?;
+
+    let mut attempt = 1;
+    loop {
+        // Every try queues for its host again, so retries don't burst past the limits either
+        let permit = http.limiter().acquire(&url, rate_limit).await;
+        // Bodies are JSON or strings, so the request can always be cloned for another try
+        let Some(attempt_request) = request.try_clone() else {
+            return execute_request(&client, request, &url, encoding, charset.as_deref()).await;
+        };
+        match execute_request(&client, attempt_request, &url, encoding, charset.as_deref()).await {
+            Ok(response) if response.status >= 500 && retry.should_retry(Some(RetryableError::ServerError), attempt) => {
+                log::warn!("[Rust] {} returned {}, retrying (attempt {}/{})", url, response.status, attempt, retry.max_attempts);
+            }
+            Ok(mut response) => {
+                response.attempts = attempt;
+                return Ok(response);
+            }
+            Err(e) if retry.should_retry(RetryableError::from_kind(e.kind), attempt) => {
+                log::warn!("[Rust] {} failed: {}, retrying (attempt {}/{})", url, e.error, attempt, retry.max_attempts);
+            }
+            Err(mut e) => {
+                e.attempts = Some(attempt);
+                return Err(e);
+            }
+        }
+        drop(permit);
+        tokio::time::sleep(retry.backoff(attempt)).await;
+        attempt += 1;
+    }
+}
+
+fn build_request(client: &reqwest::Client, options: HttpRequestOptions) -> Result<reqwest::Request, HttpError> {
+    // Per-request timeout on top of the pooled client
+    let timeout_duration = std::time::Duration::from_secs(options.timeout_secs.unwrap_or(http_client::DEFAULT_TIMEOUT_SECS));
 
     let method_str = options.method.unwrap_or_else(|| "GET".to_string());
     let method_for_reqwest = match method_str.to_uppercase().as_str() {
@@ -365,7 +453,7 @@ async fn make_http_request(options: HttpRequestOptions) -> Result<HttpResponse,
         _ => reqwest::Method::GET,
     };
 
-    let mut request_builder = client.request(method_for_reqwest.clone(), &options.url);
+    let mut request_builder = client.request(method_for_reqwest.clone(), &options.url).timeout(timeout_duration);
 
     let mut req_headers = HeaderMap::new();
     req_headers.insert(USER_AGENT, HeaderValue::from_static("LibreTV-TauriApp/1.0"));
@@ -376,10 +464,10 @@ async fn make_http_request(options: HttpRequestOptions) -> Result<HttpResponse,
                 if let Ok(header_value) = HeaderValue::from_str(&value) {
                     req_headers.insert(header_name, header_value);
                 } else {
-                    eprintln!("Invalid header value for {}: {}", key, value);
+                    log::warn!("[Rust] Invalid header value for {}: {}", key, value);
                 }
             } else {
-                 eprintln!("Invalid header name: {}", key);
+                 log::warn!("[Rust] Invalid header name: {}", key);
             }
         }
     }
@@ -388,7 +476,7 @@ async fn make_http_request(options: HttpRequestOptions) -> Result<HttpResponse,
     if let Some(body_value) = options.body {
         let content_type_is_json = req_headers
             .get(CONTENT_TYPE)
-            .map_or(false, |ct| ct.to_str().unwrap_or("").contains("application/json"));
+            .is_some_and(|ct| ct.to_str().unwrap_or("").contains("application/json"));
 
         if method_str.eq_ignore_ascii_case("POST") || method_str.eq_ignore_ascii_case("PUT") || method_str.eq_ignore_ascii_case("PATCH") { // Removed unnecessary parentheses
             if content_type_is_json {
@@ -400,8 +488,19 @@ async fn make_http_request(options: HttpRequestOptions) -> Result<HttpResponse,
             }
         }
     }
-    
-    match request_builder.send().await {
+
+    request_builder.build().map_err(|e| HttpError::new(HttpErrorKind::InvalidRequest, "Request builder error", Some(e.to_string())))
+}
+
+// One try of a request; send_http_request decides from the error kind whether to try again.
+async fn execute_request(
+    client: &reqwest::Client,
+    request: reqwest::Request,
+    url: &str,
+    encoding: BodyEncoding,
+    charset: Option<&str>,
+) -> Result<HttpResponse, HttpError> {
+    match client.execute(request).await {
         Ok(response) => {
             let status = response.status().as_u16();
             let mut resp_headers = HashMap::new();
@@ -410,24 +509,38 @@ async fn make_http_request(options: HttpRequestOptions) -> Result<HttpResponse,
                     resp_headers.insert(key.as_str().to_string(), val_str.to_string());
                 }
             }
-            
-            match response.text().await {
-                Ok(text_body) => {
-                    log::debug!("[Rust] make_http_request successful for URL: {}. Status: {}. Response body (first 100 chars): {:.100}", options.url, status, text_body);
+            let content_type = resp_headers.get("content-type").cloned();
+
+            let body = match encoding {
+                // Not response.text(): it trusts the Content-Type charset, which Chinese sources often get wrong
+                BodyEncoding::Text => response
+                    .bytes()
+                    .await
+                    .map(|bytes| (bytes.len(), charset::decode(&bytes, content_type.as_deref(), charset))),
+                BodyEncoding::Base64 => response.bytes().await.map(|bytes| (bytes.len(), BASE64_STANDARD.encode(&bytes))),
+            };
+            match body {
+                Ok((content_length, body)) => {
+                    log::debug!("[Rust] make_http_request successful for URL: {}. Status: {}. Response body (first 100 chars): {:.100}", url, status, body);
                     Ok(HttpResponse {
                         status,
                         headers: resp_headers,
-                        body: text_body,
+                        body,
+                        body_encoding: encoding,
+                        content_type,
+                        content_length,
+                        attempts: 1,
+                        cache: None,
                     })
                 },
                 Err(e) => {
-                    log::error!("[Rust] make_http_request failed to read response body for URL: {}. Error: {}", options.url, e);
-                    Err(HttpError { error: "Failed to read response body".to_string(), details: Some(e.to_string()) })
+                    log::error!("[Rust] make_http_request failed to read response body for URL: {}. Error: {}", url, e);
+                    Err(HttpError::new(HttpErrorKind::from_reqwest(&e), "Failed to read response body", Some(e.to_string())))
                 }
             }
         }
         Err(e) => {
-            log::error!("[Rust] make_http_request failed for URL: {}. Error: {}", options.url, e);
+            log::error!("[Rust] make_http_request failed for URL: {}. Error: {}", url, e);
             let error_details = e.to_string();
             let error_type = if e.is_timeout() { "Request timed out".to_string() }
                 else if e.is_connect() { "Connection error".to_string() }
@@ -437,7 +550,7 @@ async fn make_http_request(options: HttpRequestOptions) -> Result<HttpResponse,
                 else if e.is_body() { "Response body error".to_string() }
                 else if e.is_decode() { "Response decoding error".to_string() }
                 else { "HTTP request failed".to_string() };
-            Err(HttpError { error: error_type, details: Some(error_details) })
+            Err(HttpError::new(HttpErrorKind::from_reqwest(&e), error_type, Some(error_details)))
         }
     }
 }
@@ -455,6 +568,15 @@ pub fn run() {
   // }
   
   tauri::Builder::default()
+    .manage(probe::ProbeCache::default())
+    .manage(RequestGroups::default())
+    // Playlists, segments and keys for hls.js, fetched from Rust (see hls_proxy.rs)
+    .register_asynchronous_uri_scheme_protocol(hls_proxy::SCHEME, |ctx, request, responder| {
+      let http = ctx.app_handle().state::<HttpClient>().inner().clone();
+      tauri::async_runtime::spawn(async move {
+        responder.respond(hls_proxy::handle(&http, request).await);
+      });
+    })
     // .plugin( // Temporarily commented out
         // log_builder
             // .level(log::LevelFilter::Debug) 
@@ -462,14 +584,125 @@ pub fn run() {
     // )
     .invoke_handler(tauri::generate_handler![
         make_http_request,
-        search_videos
+        search_videos,
+        get_video_detail,
+        search::search_all_sources,
+        sources::list_sources,
+        sources::add_source,
+        sources::update_source,
+        sources::remove_source,
+        sources::reload_sources,
+        ad_filter::filter_m3u8_ads,
+        probe::probe_sources,
+        stream_probe::probe_streams,
+        cancel::cancel_requests,
+        http_cache::inspect_http_cache,
+        http_cache::clear_http_cache,
+        rate_limit::get_rate_limits,
+        rate_limit::set_rate_limits,
+        proxy::get_proxy_settings,
+        proxy::set_proxy_settings
         // 如果您有其他 command，请在此处添加，用逗号分隔
         // e.g., another_command, yet_another_command
     ])
-    .setup(|_app| {
+    .setup(|app| {
       // 可以在这里执行应用启动时的设置代码
+      // Source registry lives in the app data dir so users can edit it without a new build
+      let sources_path = app.path().app_data_dir().ok().map(|dir| dir.join(sources::SOURCES_FILE_NAME));
+      let registry = SourceRegistry::new(sources_path);
+      if let Err(e) = registry.load() {
+          log::error!("[Rust] Failed to load source registry, using built-in sources: {} {:?}", e.error, e.details);
+      }
+      // Shared client, with the response cache when the app cache dir is available
+      let mut http = HttpClient::new().expect("failed to build HTTP client");
+      match app.path().app_cache_dir() {
+          Ok(dir) => http = http.with_cache(HttpCache::new(dir.join(http_cache::CACHE_DIR_NAME))),
+          Err(e) => log::warn!("[Rust] No app cache dir, HTTP responses won't be cached: {}", e),
+      }
+      // Sources with their own limits and proxy routes get them before the frontend sends the first request
+      sources::apply_host_settings(&http, &registry.list());
+      app.manage(registry);
+      app.manage(http);
       Ok(())
     })
     .run(tauri::generate_context!())
     .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_kind_serializes_adjacently_tagged() {
+        // The frontend picks its message from error.kind.code and error.kind.status
+        let error = HttpError::new(HttpErrorKind::Status(404), "API request failed with status: 404", None);
+        let json = serde_json::to_value(&error).unwrap();
+        assert_eq!(json["kind"], serde_json::json!({ "code": "status", "status": 404 }));
+        assert_eq!(json["error"], "API request failed with status: 404");
+        assert!(json.get("attempts").is_none());
+
+        let timeout = serde_json::to_value(HttpErrorKind::Timeout).unwrap();
+        assert_eq!(timeout, serde_json::json!({ "code": "timeout" }));
+        let unknown = serde_json::to_value(HttpErrorKind::UnknownSource).unwrap();
+        assert_eq!(unknown, serde_json::json!({ "code": "unknown_source" }));
+
+        let parsed: HttpErrorKind = serde_json::from_value(serde_json::json!({ "code": "status", "status": 502 })).unwrap();
+        assert_eq!(parsed, HttpErrorKind::Status(502));
+    }
+
+    // Answers the first `failures` requests with 503 and every later one with 200, one request per connection
+    async fn flaky_server(failures: usize) -> String {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let served = std::sync::Arc::new(AtomicUsize::new(0));
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let served = served.clone();
+                tokio::spawn(async move {
+                    let mut buf = Vec::new();
+                    let mut chunk = [0u8; 1024];
+                    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+                        match socket.read(&mut chunk).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(read) => buf.extend_from_slice(&chunk[..read]),
+                        }
+                    }
+                    let status = if served.fetch_add(1, Ordering::SeqCst) < failures { "503 Service Unavailable" } else { "200 OK" };
+                    let response = format!("HTTP/1.1 {}\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok", status);
+                    let _ = socket.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        format!("http://{}/api.php", addr)
+    }
+
+    fn retried(url: String, max_attempts: u32) -> HttpRequestOptions {
+        let retry = RetryPolicy { max_attempts, initial_backoff_ms: 1, ..Default::default() };
+        HttpRequestOptions { url, timeout_secs: Some(5), retry: Some(retry), ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn reports_attempts_of_retried_requests() {
+        let http = HttpClient::new().unwrap();
+
+        let response = fetch_with_retry(&http, retried(flaky_server(2).await, 3)).await.unwrap();
+        assert_eq!((response.status, response.attempts), (200, 3));
+        // Out of tries, the last 5xx answer is returned as is
+        let response = fetch_with_retry(&http, retried(flaky_server(5).await, 2)).await.unwrap();
+        assert_eq!((response.status, response.attempts), (503, 2));
+        let response = fetch_with_retry(&http, HttpRequestOptions { retry: None, ..retried(flaky_server(0).await, 1) }).await.unwrap();
+        assert_eq!(response.attempts, 1);
+
+        // Nothing listens on the port of a dropped listener
+        let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+        let error = fetch_with_retry(&http, retried(format!("http://{}/", closed), 2)).await.unwrap_err();
+        assert_eq!((error.kind, error.attempts), (HttpErrorKind::Connect, Some(2)));
+
+        let error = fetch_with_retry(&http, retried(format!("http://{}/", closed), 0)).await.unwrap_err();
+        assert_eq!((error.kind, error.attempts), (HttpErrorKind::InvalidRequest, None));
+    }
+}
@@ -0,0 +1,804 @@
+use std::fmt;
+
+use crate::{HttpError, HttpErrorKind};
+
+// --- HLS playlists (RFC 8216) ---
+//
+// Playlists are parsed into typed tags that keep their original order and spelling, so writing a
+// parsed playlist back yields the same text. Tags we don't model are kept verbatim as `Tag::Other`,
+// and so are numeric header tags with values packagers get wrong (`#EXT-X-TARGETDURATION:10.0`).
+// Blank lines are dropped and line endings are normalized to `\n`.
+
+fn invalid_playlist(line: usize, reason: String) -> HttpError {
+    HttpError::new(HttpErrorKind::Decode, "Invalid M3U8 playlist", Some(format!("line {}: {}", line, reason)))
+}
+
+/// A decimal as written in the playlist, e.g. an `EXTINF` duration. The original text is kept so
+/// `10.000000` isn't written back as `10`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Decimal {
+    value: f64,
+    text: String,
+}
+
+impl Decimal {
+    fn parse(text: &str) -> Option<Self> {
+        let value = text.parse::<f64>().ok().filter(|value| value.is_finite())?;
+        Some(Decimal { value, text: text.to_string() })
+    }
+
+    pub(crate) fn value(&self) -> f64 {
+        self.value
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum AttributeValue {
+    Quoted(String),
+    Unquoted(String), // Enumerated strings, numbers, hex sequences and resolutions
+}
+
+/// `NAME=VALUE` pairs of tags like `EXT-X-KEY`, in their original order.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct AttributeList(Vec<(String, AttributeValue)>);
+
+impl AttributeList {
+    fn parse(text: &str, line: usize) -> Result<Self, HttpError> {
+        let mut attributes = Vec::new();
+        let mut rest = text.trim();
+        while !rest.is_empty() {
+            let (name, after_name) = rest
+                .split_once('=')
+                .ok_or_else(|| invalid_playlist(line, format!("attribute without value in '{}'", text)))?;
+            let name = name.trim();
+            if name.is_empty() {
+                return Err(invalid_playlist(line, format!("attribute without name in '{}'", text)));
+            }
+            let (value, after_value) = match after_name.strip_prefix('"') {
+                Some(quoted) => {
+                    let end = quoted
+                        .find('"')
+                        .ok_or_else(|| invalid_playlist(line, format!("unterminated quoted string in '{}'", text)))?;
+                    (AttributeValue::Quoted(quoted[..end].to_string()), &quoted[end + 1..])
+                }
+                None => {
+                    let end = after_name.find(',').unwrap_or(after_name.len());
+                    (AttributeValue::Unquoted(after_name[..end].trim().to_string()), &after_name[end..])
+                }
+            };
+            attributes.push((name.to_string(), value));
+            rest = after_value.trim_start();
+            if let Some(next) = rest.strip_prefix(',') {
+                rest = next.trim_start();
+            } else if !rest.is_empty() {
+                return Err(invalid_playlist(line, format!("expected ',' before '{}'", rest)));
+            }
+        }
+        Ok(AttributeList(attributes))
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&str> {
+        self.0.iter().find(|(key, _)| key == name).map(|(_, value)| match value {
+            AttributeValue::Quoted(value) | AttributeValue::Unquoted(value) => value.as_str(),
+        })
+    }
+
+    /// Sets a quoted-string attribute, replacing it in place or appending it.
+    pub(crate) fn set_quoted(&mut self, name: &str, value: &str) {
+        let value = AttributeValue::Quoted(value.to_string());
+        match self.0.iter_mut().find(|(key, _)| key == name) {
+            Some((_, existing)) => *existing = value,
+            None => self.0.push((name.to_string(), value)),
+        }
+    }
+}
+
+impl fmt::Display for AttributeList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, (name, value)) in self.0.iter().enumerate() {
+            if index > 0 {
+                f.write_str(",")?;
+            }
+            match value {
+                AttributeValue::Quoted(value) => write!(f, "{}=\"{}\"", name, value)?,
+                AttributeValue::Unquoted(value) => write!(f, "{}={}", name, value)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `<length>[@<offset>]` of `EXT-X-BYTERANGE` and the `BYTERANGE` attribute of `EXT-X-MAP`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ByteRange {
+    pub(crate) length: u64,
+    /// Without an offset the range starts right after the previous segment's range.
+    pub(crate) offset: Option<u64>,
+}
+
+impl ByteRange {
+    fn parse(text: &str) -> Option<Self> {
+        let (length, offset) = match text.split_once('@') {
+            Some((length, offset)) => (length, Some(offset.trim().parse().ok()?)),
+            None => (text, None),
+        };
+        Some(ByteRange { length: length.trim().parse().ok()?, offset })
+    }
+}
+
+impl fmt::Display for ByteRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.offset {
+            Some(offset) => write!(f, "{}@{}", self.length, offset),
+            None => write!(f, "{}", self.length),
+        }
+    }
+}
+
+/// `EXT-X-KEY` (and `EXT-X-SESSION-KEY`): how the following segments are encrypted.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Key {
+    pub(crate) attributes: AttributeList,
+}
+
+/// `EXT-X-MAP`: media initialization section, e.g. the fMP4 header.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Map {
+    pub(crate) attributes: AttributeList,
+}
+
+/// `EXT-X-STREAM-INF` together with the URI line after it.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Variant {
+    pub(crate) attributes: AttributeList,
+    pub(crate) uri: String,
+}
+
+impl Variant {
+    pub(crate) fn bandwidth(&self) -> Option<u64> {
+        self.attributes.get("BANDWIDTH").and_then(|value| value.parse().ok())
+    }
+
+    /// `RESOLUTION=1280x720` as `(1280, 720)`.
+    pub(crate) fn resolution(&self) -> Option<(u32, u32)> {
+        let (width, height) = self.attributes.get("RESOLUTION")?.split_once(['x', 'X'])?;
+        Some((width.parse().ok()?, height.parse().ok()?))
+    }
+}
+
+/// One line of a playlist after `#EXTM3U`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Tag {
+    Version(u32),
+    TargetDuration(u64),
+    MediaSequence(u64),
+    DiscontinuitySequence(u64),
+    PlaylistType(String),
+    IndependentSegments,
+    IFramesOnly,
+    EndList,
+    /// `#EXTINF:<duration>,[<title>]`; `title` is `None` when even the comma is missing.
+    Inf { duration: Decimal, title: Option<String> },
+    ByteRange(ByteRange),
+    Discontinuity,
+    Key(Key),
+    Map(Map),
+    ProgramDateTime(String),
+    /// `EXT-X-MEDIA` rendition (alternative audio, subtitles…).
+    Media(AttributeList),
+    StreamInf(Variant),
+    IFrameStreamInf(AttributeList),
+    SessionKey(Key),
+    /// Any other `#EXT` tag, kept verbatim. `value` is everything after the first `:`.
+    Other { name: String, value: Option<String> },
+    /// A `#` line that isn't a tag, without the leading `#`.
+    Comment(String),
+}
+
+impl Tag {
+    fn parse(line: &str, line_number: usize) -> Result<Tag, HttpError> {
+        let Some(tag) = line.strip_prefix("#EXT") else {
+            return Ok(Tag::Comment(line[1..].to_string()));
+        };
+        let (name, value) = match tag.split_once(':') {
+            Some((name, value)) => (format!("#EXT{}", name), Some(value)),
+            None => (line.to_string(), None),
+        };
+        let invalid = |what: &str| invalid_playlist(line_number, format!("invalid {} in '{}'", what, line));
+        let number = || value.and_then(|value| value.trim().parse::<u64>().ok());
+        let attributes = || AttributeList::parse(value.unwrap_or_default(), line_number);
+        let other = || Tag::Other { name: name.clone(), value: value.map(str::to_string) };
+
+        Ok(match name.as_str() {
+            "#EXT-X-VERSION" => number().map_or_else(other, |version| Tag::Version(version as u32)),
+            "#EXT-X-TARGETDURATION" => number().map_or_else(other, Tag::TargetDuration),
+            "#EXT-X-MEDIA-SEQUENCE" => number().map_or_else(other, Tag::MediaSequence),
+            "#EXT-X-DISCONTINUITY-SEQUENCE" => number().map_or_else(other, Tag::DiscontinuitySequence),
+            "#EXT-X-PLAYLIST-TYPE" => Tag::PlaylistType(value.unwrap_or_default().trim().to_string()),
+            "#EXT-X-INDEPENDENT-SEGMENTS" => Tag::IndependentSegments,
+            "#EXT-X-I-FRAMES-ONLY" => Tag::IFramesOnly,
+            "#EXT-X-ENDLIST" => Tag::EndList,
+            "#EXTINF" => {
+                let value = value.ok_or_else(|| invalid("duration"))?;
+                let (duration, title) = match value.split_once(',') {
+                    Some((duration, title)) => (duration, Some(title.to_string())),
+                    None => (value, None),
+                };
+                Tag::Inf { duration: Decimal::parse(duration.trim()).ok_or_else(|| invalid("duration"))?, title }
+            }
+            "#EXT-X-BYTERANGE" => Tag::ByteRange(value.and_then(ByteRange::parse).ok_or_else(|| invalid("byte range"))?),
+            "#EXT-X-DISCONTINUITY" => Tag::Discontinuity,
+            "#EXT-X-KEY" => Tag::Key(Key { attributes: attributes()? }),
+            "#EXT-X-MAP" => Tag::Map(Map { attributes: attributes()? }),
+            "#EXT-X-PROGRAM-DATE-TIME" => Tag::ProgramDateTime(value.unwrap_or_default().to_string()),
+            "#EXT-X-MEDIA" => Tag::Media(attributes()?),
+            // The URI is filled in from the next line by the master playlist parser
+            "#EXT-X-STREAM-INF" => Tag::StreamInf(Variant { attributes: attributes()?, uri: String::new() }),
+            "#EXT-X-I-FRAME-STREAM-INF" => Tag::IFrameStreamInf(attributes()?),
+            "#EXT-X-SESSION-KEY" => Tag::SessionKey(Key { attributes: attributes()? }),
+            _ => other(),
+        })
+    }
+
+    // Tags describing the whole media playlist rather than the segment after them
+    fn is_playlist_tag(&self) -> bool {
+        matches!(
+            self,
+            Tag::Version(_)
+                | Tag::TargetDuration(_)
+                | Tag::MediaSequence(_)
+                | Tag::DiscontinuitySequence(_)
+                | Tag::PlaylistType(_)
+                | Tag::IndependentSegments
+                | Tag::IFramesOnly
+                | Tag::EndList
+                | Tag::Comment(_)
+        ) || matches!(self, Tag::Other { name, .. } if matches!(
+            name.as_str(),
+            "#EXT-X-START" | "#EXT-X-VERSION" | "#EXT-X-TARGETDURATION" | "#EXT-X-MEDIA-SEQUENCE" | "#EXT-X-DISCONTINUITY-SEQUENCE"
+        ))
+    }
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Tag::Version(version) => write!(f, "#EXT-X-VERSION:{}", version),
+            Tag::TargetDuration(duration) => write!(f, "#EXT-X-TARGETDURATION:{}", duration),
+            Tag::MediaSequence(sequence) => write!(f, "#EXT-X-MEDIA-SEQUENCE:{}", sequence),
+            Tag::DiscontinuitySequence(sequence) => write!(f, "#EXT-X-DISCONTINUITY-SEQUENCE:{}", sequence),
+            Tag::PlaylistType(playlist_type) => write!(f, "#EXT-X-PLAYLIST-TYPE:{}", playlist_type),
+            Tag::IndependentSegments => f.write_str("#EXT-X-INDEPENDENT-SEGMENTS"),
+            Tag::IFramesOnly => f.write_str("#EXT-X-I-FRAMES-ONLY"),
+            Tag::EndList => f.write_str("#EXT-X-ENDLIST"),
+            Tag::Inf { duration, title: Some(title) } => write!(f, "#EXTINF:{},{}", duration, title),
+            Tag::Inf { duration, title: None } => write!(f, "#EXTINF:{}", duration),
+            Tag::ByteRange(range) => write!(f, "#EXT-X-BYTERANGE:{}", range),
+            Tag::Discontinuity => f.write_str("#EXT-X-DISCONTINUITY"),
+            Tag::Key(key) => write!(f, "#EXT-X-KEY:{}", key.attributes),
+            Tag::Map(map) => write!(f, "#EXT-X-MAP:{}", map.attributes),
+            Tag::ProgramDateTime(time) => write!(f, "#EXT-X-PROGRAM-DATE-TIME:{}", time),
+            Tag::Media(attributes) => write!(f, "#EXT-X-MEDIA:{}", attributes),
+            Tag::StreamInf(variant) => write!(f, "#EXT-X-STREAM-INF:{}\n{}", variant.attributes, variant.uri),
+            Tag::IFrameStreamInf(attributes) => write!(f, "#EXT-X-I-FRAME-STREAM-INF:{}", attributes),
+            Tag::SessionKey(key) => write!(f, "#EXT-X-SESSION-KEY:{}", key.attributes),
+            Tag::Other { name, value: Some(value) } => write!(f, "{}:{}", name, value),
+            Tag::Other { name, value: None } => f.write_str(name),
+            Tag::Comment(comment) => write!(f, "#{}", comment),
+        }
+    }
+}
+
+/// A media segment: the tags directly before its URI line, and the URI.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MediaSegment {
+    pub(crate) tags: Vec<Tag>,
+    pub(crate) uri: String,
+}
+
+impl MediaSegment {
+    pub(crate) fn duration(&self) -> f64 {
+        self.tags
+            .iter()
+            .find_map(|tag| match tag {
+                Tag::Inf { duration, .. } => Some(duration.value()),
+                _ => None,
+            })
+            .unwrap_or(0.0)
+    }
+
+    /// Whether an `EXT-X-DISCONTINUITY` precedes this segment.
+    pub(crate) fn discontinuity(&self) -> bool {
+        self.tags.contains(&Tag::Discontinuity)
+    }
+
+    /// Key declared right before this segment. Keys stay in effect for later segments, see
+    /// `MediaPlaylist::key_for`.
+    pub(crate) fn key(&self) -> Option<&Key> {
+        self.tags.iter().rev().find_map(|tag| match tag {
+            Tag::Key(key) => Some(key),
+            _ => None,
+        })
+    }
+
+    /// Media initialization section declared right before this segment, see
+    /// `MediaPlaylist::map_for`.
+    pub(crate) fn map(&self) -> Option<&Map> {
+        self.tags.iter().rev().find_map(|tag| match tag {
+            Tag::Map(map) => Some(map),
+            _ => None,
+        })
+    }
+}
+
+/// Playlist of media segments.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct MediaPlaylist {
+    /// Playlist-level tags before the first segment (`EXT-X-VERSION`, `EXT-X-TARGETDURATION`…).
+    pub(crate) tags: Vec<Tag>,
+    pub(crate) segments: Vec<MediaSegment>,
+    /// Tags after the last URI, usually `EXT-X-ENDLIST`.
+    pub(crate) end_tags: Vec<Tag>,
+}
+
+impl MediaPlaylist {
+    /// Key in effect for the segment at `index`: the last `EXT-X-KEY` at or before it. `None`
+    /// without a key or for an index past the last segment.
+    pub(crate) fn key_for(&self, index: usize) -> Option<&Key> {
+        self.segments.get(..=index)?.iter().rev().find_map(MediaSegment::key)
+    }
+
+    /// `EXT-X-MAP` in effect for the segment at `index`, like `key_for`.
+    pub(crate) fn map_for(&self, index: usize) -> Option<&Map> {
+        self.segments.get(..=index)?.iter().rev().find_map(MediaSegment::map)
+    }
+}
+
+/// Playlist listing variant streams and renditions.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct MasterPlaylist {
+    pub(crate) tags: Vec<Tag>,
+}
+
+impl MasterPlaylist {
+    pub(crate) fn variants(&self) -> impl Iterator<Item = &Variant> {
+        self.tags.iter().filter_map(|tag| match tag {
+            Tag::StreamInf(variant) => Some(variant),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Playlist {
+    Master(MasterPlaylist),
+    Media(MediaPlaylist),
+}
+
+impl Playlist {
+    /// Parses playlist text; a master playlist is recognized by its `EXT-X-STREAM-INF`,
+    /// `EXT-X-I-FRAME-STREAM-INF` or `EXT-X-MEDIA` tags.
+    pub(crate) fn parse(text: &str) -> Result<Playlist, HttpError> {
+        let mut lines = text
+            .trim_start_matches('\u{feff}')
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty());
+        match lines.next() {
+            Some((_, "#EXTM3U")) => {}
+            Some((line, _)) => return Err(invalid_playlist(line, "playlist must start with #EXTM3U".to_string())),
+            None => return Err(invalid_playlist(1, "playlist is empty".to_string())),
+        }
+
+        let mut tags = Vec::new();
+        let mut uris = Vec::new();
+        for (line_number, line) in lines {
+            if line.starts_with('#') {
+                tags.push((line_number, Some(Tag::parse(line, line_number)?)));
+            } else {
+                tags.push((line_number, None));
+                uris.push(line);
+            }
+        }
+        let is_master = tags
+            .iter()
+            .any(|(_, tag)| matches!(tag, Some(Tag::StreamInf(_) | Tag::IFrameStreamInf(_) | Tag::Media(_))));
+
+        let mut uris = uris.into_iter();
+        if is_master {
+            let mut playlist = MasterPlaylist::default();
+            let mut pending: Option<Variant> = None;
+            for (line_number, tag) in tags {
+                match (tag, pending.take()) {
+                    (None, Some(mut variant)) => {
+                        variant.uri = uris.next().unwrap_or_default().to_string();
+                        playlist.tags.push(Tag::StreamInf(variant));
+                    }
+                    (Some(Tag::StreamInf(variant)), None) => pending = Some(variant),
+                    (Some(tag), None) => playlist.tags.push(tag),
+                    (None, None) => {
+                        return Err(invalid_playlist(line_number, "URI in master playlist without EXT-X-STREAM-INF".to_string()))
+                    }
+                    (Some(_), Some(_)) => {
+                        return Err(invalid_playlist(line_number, "EXT-X-STREAM-INF must be followed by a URI".to_string()))
+                    }
+                }
+            }
+            if pending.is_some() {
+                return Err(invalid_playlist(text.lines().count(), "EXT-X-STREAM-INF must be followed by a URI".to_string()));
+            }
+            Ok(Playlist::Master(playlist))
+        } else {
+            let mut playlist = MediaPlaylist::default();
+            let mut pending = Vec::new();
+            for (_, tag) in tags {
+                match tag {
+                    Some(tag) if playlist.segments.is_empty() && pending.is_empty() && tag.is_playlist_tag() => playlist.tags.push(tag),
+                    Some(tag) => pending.push(tag),
+                    None => playlist.segments.push(MediaSegment {
+                        tags: std::mem::take(&mut pending),
+                        uri: uris.next().unwrap_or_default().to_string(),
+                    }),
+                }
+            }
+            playlist.end_tags = pending;
+            Ok(Playlist::Media(playlist))
+        }
+    }
+}
+
+impl Playlist {
+    /// Replaces every URI in the playlist with `f(uri)`: segment and variant URIs, and the `URI`
+    /// attributes of `EXT-X-KEY`, `EXT-X-MAP`, `EXT-X-MEDIA`, `EXT-X-I-FRAME-STREAM-INF` and
+    /// `EXT-X-SESSION-KEY`.
+    pub(crate) fn map_uris(&mut self, mut f: impl FnMut(&str) -> String) {
+        let tags: Vec<&mut Tag> = match self {
+            Playlist::Master(playlist) => playlist.tags.iter_mut().collect(),
+            Playlist::Media(playlist) => {
+                for segment in &mut playlist.segments {
+                    segment.uri = f(&segment.uri);
+                }
+                playlist
+                    .tags
+                    .iter_mut()
+                    .chain(playlist.segments.iter_mut().flat_map(|segment| segment.tags.iter_mut()))
+                    .chain(playlist.end_tags.iter_mut())
+                    .collect()
+            }
+        };
+        for tag in tags {
+            let attributes = match tag {
+                Tag::StreamInf(variant) => {
+                    variant.uri = f(&variant.uri);
+                    continue;
+                }
+                Tag::Key(Key { attributes })
+                | Tag::SessionKey(Key { attributes })
+                | Tag::Map(Map { attributes })
+                | Tag::Media(attributes)
+                | Tag::IFrameStreamInf(attributes) => attributes,
+                _ => continue,
+            };
+            if let Some(uri) = attributes.get("URI") {
+                let uri = f(uri);
+                attributes.set_quoted("URI", &uri);
+            }
+        }
+    }
+}
+
+impl Playlist {
+    /// Resolves every URI (see `map_uris`) against `base`, the URL the playlist was finally served
+    /// from after redirects, so it can be loaded from anywhere else. With `proxy_prefix` the absolute
+    /// URL is percent-encoded and appended to the prefix, e.g. `/proxy?url=`. URIs that don't
+    /// resolve to http(s), such as `data:` or `skd:` keys, are left as they are.
+    pub(crate) fn resolve_uris(&mut self, base: &reqwest::Url, proxy_prefix: Option<&str>) {
+        self.map_uris(|uri| resolve_uri(uri, base, proxy_prefix));
+    }
+}
+
+/// One URI as `Playlist::resolve_uris` rewrites it.
+pub(crate) fn resolve_uri(uri: &str, base: &reqwest::Url, proxy_prefix: Option<&str>) -> String {
+    match base.join(uri) {
+        Ok(absolute) if matches!(absolute.scheme(), "http" | "https") => match proxy_prefix {
+            Some(prefix) => format!("{}{}", prefix, urlencoding::encode(absolute.as_str())),
+            None => absolute.to_string(),
+        },
+        _ => uri.to_string(),
+    }
+}
+
+/// Fallback of `Playlist::resolve_uris` for text `Playlist::parse` rejects: resolves every URI line
+/// and every `URI="…"` attribute line by line, leaving everything else as it is.
+pub(crate) fn resolve_uris_in_text(text: &str, base: &reqwest::Url, proxy_prefix: Option<&str>) -> String {
+    let mut resolved = String::with_capacity(text.len());
+    for line in text.trim_start_matches('\u{feff}').lines().map(str::trim).filter(|line| !line.is_empty()) {
+        if !line.starts_with('#') {
+            resolved.push_str(&resolve_uri(line, base, proxy_prefix));
+        } else {
+            let mut rest = line;
+            while let Some(start) = rest.find("URI=\"") {
+                let value_start = start + "URI=\"".len();
+                let Some(length) = rest[value_start..].find('"') else {
+                    break;
+                };
+                resolved.push_str(&rest[..value_start]);
+                resolved.push_str(&resolve_uri(&rest[value_start..value_start + length], base, proxy_prefix));
+                rest = &rest[value_start + length..];
+            }
+            resolved.push_str(rest);
+        }
+        resolved.push('\n');
+    }
+    resolved
+}
+
+impl fmt::Display for Playlist {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "#EXTM3U")?;
+        match self {
+            Playlist::Master(playlist) => {
+                for tag in &playlist.tags {
+                    writeln!(f, "{}", tag)?;
+                }
+            }
+            Playlist::Media(playlist) => {
+                for tag in &playlist.tags {
+                    writeln!(f, "{}", tag)?;
+                }
+                for segment in &playlist.segments {
+                    for tag in &segment.tags {
+                        writeln!(f, "{}", tag)?;
+                    }
+                    writeln!(f, "{}", segment.uri)?;
+                }
+                for tag in &playlist.end_tags {
+                    writeln!(f, "{}", tag)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MASTER: &str = include_str!("../tests/fixtures/m3u8/master.m3u8");
+    const MEDIA_VOD: &str = include_str!("../tests/fixtures/m3u8/media_vod.m3u8");
+    const MEDIA_ADS: &str = include_str!("../tests/fixtures/m3u8/media_ads.m3u8");
+    const MEDIA_ENCRYPTED: &str = include_str!("../tests/fixtures/m3u8/media_encrypted.m3u8");
+    const MEDIA_BYTERANGE: &str = include_str!("../tests/fixtures/m3u8/media_byterange.m3u8");
+    const MEDIA_LIVE: &str = include_str!("../tests/fixtures/m3u8/media_live.m3u8");
+    const MEDIA_LOOSE_HEADER: &str = include_str!("../tests/fixtures/m3u8/media_loose_header.m3u8");
+
+    const CORPUS: [&str; 7] = [MASTER, MEDIA_VOD, MEDIA_ADS, MEDIA_ENCRYPTED, MEDIA_BYTERANGE, MEDIA_LIVE, MEDIA_LOOSE_HEADER];
+
+    fn media(text: &str) -> MediaPlaylist {
+        match Playlist::parse(text).unwrap() {
+            Playlist::Media(playlist) => playlist,
+            Playlist::Master(_) => panic!("expected a media playlist"),
+        }
+    }
+
+    fn renditions(playlist: &MasterPlaylist) -> Vec<&AttributeList> {
+        playlist.tags.iter().filter_map(|tag| match tag {
+            Tag::Media(attributes) => Some(attributes),
+            _ => None,
+        }).collect()
+    }
+
+    fn key_uri(key: Option<&Key>) -> Option<&str> {
+        key?.attributes.get("URI")
+    }
+
+    #[test]
+    fn corpus_round_trips_losslessly() {
+        for text in CORPUS {
+            let playlist = Playlist::parse(text).unwrap();
+            assert_eq!(playlist.to_string(), text);
+            assert_eq!(Playlist::parse(&playlist.to_string()).unwrap(), playlist);
+        }
+    }
+
+    #[test]
+    fn parses_master_playlist() {
+        let Playlist::Master(playlist) = Playlist::parse(MASTER).unwrap() else {
+            panic!("expected a master playlist");
+        };
+        let variants: Vec<_> = playlist.variants().collect();
+        assert_eq!(variants.len(), 3);
+        assert_eq!(variants[0].uri, "360p/index.m3u8");
+        assert_eq!(variants[0].bandwidth(), Some(800_000));
+        assert_eq!(variants[0].attributes.get("CODECS"), Some("avc1.4d401e,mp4a.40.2"));
+        assert_eq!(variants[1].resolution(), Some((1280, 720)));
+        assert_eq!(variants[2].uri, "https://cdn.example.com/vod/1080p/index.m3u8?token=abc");
+
+        let renditions = renditions(&playlist);
+        assert_eq!(renditions.len(), 2);
+        assert_eq!(renditions[0].get("NAME"), Some("国语"));
+        assert_eq!(renditions[1].get("URI"), Some("audio/en/index.m3u8"));
+        assert!(playlist.tags.iter().any(|tag| matches!(tag, Tag::IFrameStreamInf(attributes) if attributes.get("URI") == Some("360p/iframes.m3u8"))));
+    }
+
+    #[test]
+    fn parses_vod_media_playlist() {
+        let playlist = media(MEDIA_VOD);
+        assert!(playlist.tags.contains(&Tag::TargetDuration(10)));
+        assert!(playlist.tags.contains(&Tag::MediaSequence(0)));
+        assert!(playlist.end_tags.contains(&Tag::EndList));
+        assert_eq!(playlist.segments.len(), 4);
+        assert_eq!(playlist.segments[2].uri, "0000002.ts");
+        assert_eq!(playlist.segments[2].duration(), 9.96);
+        let total: f64 = playlist.segments.iter().map(MediaSegment::duration).sum();
+        assert!((total - 34.08).abs() < 1e-9);
+    }
+
+    #[test]
+    fn keeps_discontinuities_on_their_segments() {
+        let playlist = media(MEDIA_ADS);
+        let discontinuities: Vec<_> = playlist.segments.iter().map(MediaSegment::discontinuity).collect();
+        assert_eq!(discontinuities, [false, false, true, false, false, true, false]);
+        assert_eq!(playlist.segments[2].uri, "https://ads.example.net/adjump/20240220/ad0001.ts");
+        assert_eq!(playlist.segments[4].duration(), 1.266667);
+    }
+
+    #[test]
+    fn tracks_keys_across_segments() {
+        let playlist = media(MEDIA_ENCRYPTED);
+        assert!(playlist.tags.contains(&Tag::MediaSequence(120)));
+
+        let first = playlist.segments[0].key().unwrap();
+        assert_eq!(first.attributes.get("METHOD"), Some("AES-128"));
+        assert_eq!(first.attributes.get("URI"), Some("https://keys.example.com/key1.key"));
+        assert_eq!(first.attributes.get("IV"), Some("0x00000000000000000000000000000078"));
+
+        assert!(playlist.segments[1].key().is_none());
+        assert_eq!(playlist.key_for(1), Some(first));
+        assert_eq!(key_uri(playlist.key_for(2)), Some("key2.key"));
+        assert_eq!(playlist.key_for(3).and_then(|key| key.attributes.get("METHOD")), Some("NONE"));
+        assert_eq!(playlist.key_for(playlist.segments.len()), None);
+    }
+
+    #[test]
+    fn parses_byte_ranges_and_map() {
+        let playlist = media(MEDIA_BYTERANGE);
+        let map = playlist.segments[0].map().unwrap();
+        assert_eq!(map.attributes.get("URI"), Some("main.mp4"));
+        assert_eq!(map.attributes.get("BYTERANGE").and_then(ByteRange::parse), Some(ByteRange { length: 720, offset: Some(0) }));
+
+        let ranges: Vec<ByteRange> = playlist
+            .segments
+            .iter()
+            .map(|segment| segment.tags.iter().find_map(|tag| match tag {
+                Tag::ByteRange(range) => Some(*range),
+                _ => None,
+            }).unwrap())
+            .collect();
+        assert_eq!(ranges[0], ByteRange { length: 1_508_440, offset: Some(720) });
+        assert_eq!(ranges[1], ByteRange { length: 1_502_126, offset: None });
+        assert_eq!(ranges[2].offset, Some(3_010_566));
+        // The byte range tag sits after EXTINF in the source and must stay there
+        assert!(matches!(playlist.segments[0].tags[..], [Tag::Map(_), Tag::Inf { .. }, Tag::ByteRange(_)]));
+    }
+
+    #[test]
+    fn keeps_unknown_tags_and_comments_of_live_playlists() {
+        let playlist = media(MEDIA_LIVE);
+        assert!(playlist.end_tags.is_empty());
+        assert!(playlist.tags.contains(&Tag::DiscontinuitySequence(12)));
+        assert!(playlist.tags.contains(&Tag::Comment(" Generated by packager 2.1".to_string())));
+        assert!(playlist.segments[1]
+            .tags
+            .iter()
+            .any(|tag| matches!(tag, Tag::Other { name, .. } if name == "#EXT-X-DATERANGE")));
+        assert_eq!(playlist.segments[0].tags[1], Tag::Inf { duration: Decimal::parse("4.000").unwrap(), title: Some("Live".to_string()) });
+        assert_eq!(playlist.segments[2].tags[2], Tag::Inf { duration: Decimal::parse("4.000").unwrap(), title: None });
+    }
+
+    #[test]
+    fn keeps_malformed_numeric_header_tags_verbatim() {
+        let playlist = media(MEDIA_LOOSE_HEADER);
+        assert!(playlist.tags.contains(&Tag::Version(3)));
+        assert!(playlist.tags.contains(&Tag::Other { name: "#EXT-X-TARGETDURATION".to_string(), value: Some("10.0".to_string()) }));
+        assert!(playlist.tags.contains(&Tag::Other { name: "#EXT-X-MEDIA-SEQUENCE".to_string(), value: Some("1,".to_string()) }));
+        assert_eq!(playlist.tags.len(), 4);
+        assert_eq!(playlist.segments.len(), 3);
+        assert!(playlist.segments.iter().all(|segment| matches!(segment.tags[..], [Tag::Inf { .. }])));
+    }
+
+    #[test]
+    fn normalizes_crlf_bom_and_blank_lines() {
+        let text = format!("\u{feff}{}", MEDIA_VOD.replace('\n', "\r\n\r\n"));
+        assert_eq!(Playlist::parse(&text).unwrap().to_string(), MEDIA_VOD);
+    }
+
+    #[test]
+    fn rewrites_attribute_uris_in_place() {
+        let mut playlist = media(MEDIA_ENCRYPTED);
+        let Tag::Key(key) = &mut playlist.segments[0].tags[0] else {
+            panic!("expected a key tag");
+        };
+        key.attributes.set_quoted("URI", "libretv://key?url=1");
+        assert_eq!(
+            playlist.segments[0].tags[0].to_string(),
+            r#"#EXT-X-KEY:METHOD=AES-128,URI="libretv://key?url=1",IV=0x00000000000000000000000000000078"#
+        );
+    }
+
+    #[test]
+    fn resolves_relative_uris_against_final_url() {
+        let base = reqwest::Url::parse("https://cdn2.example.com/20240301/abc/index.m3u8").unwrap();
+        let mut playlist = Playlist::parse("#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=1\n1000kb/hls/mixed.m3u8\n").unwrap();
+        playlist.resolve_uris(&base, None);
+        assert_eq!(
+            playlist.to_string(),
+            "#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=1\nhttps://cdn2.example.com/20240301/abc/1000kb/hls/mixed.m3u8\n"
+        );
+
+        let mut playlist = Playlist::parse(MEDIA_BYTERANGE).unwrap();
+        playlist.resolve_uris(&base, Some("/proxy?url="));
+        let Playlist::Media(media) = playlist else {
+            panic!("expected a media playlist");
+        };
+        let proxied = "/proxy?url=https%3A%2F%2Fcdn2.example.com%2F20240301%2Fabc%2Fmain.mp4";
+        assert_eq!(media.segments[0].map().and_then(|map| map.attributes.get("URI")), Some(proxied));
+        assert!(media.segments.iter().all(|segment| segment.uri == proxied));
+    }
+
+    #[test]
+    fn resolves_key_and_rendition_uris() {
+        let base = reqwest::Url::parse("https://cdn.example.com/vod/master.m3u8").unwrap();
+        let mut playlist = Playlist::parse(MASTER).unwrap();
+        playlist.resolve_uris(&base, None);
+        let Playlist::Master(master) = playlist else {
+            panic!("expected a master playlist");
+        };
+        let renditions: Vec<_> = renditions(&master).into_iter().filter_map(|rendition| rendition.get("URI")).collect();
+        assert_eq!(renditions, ["https://cdn.example.com/vod/audio/zh/index.m3u8", "https://cdn.example.com/vod/audio/en/index.m3u8"]);
+        // Already absolute URIs stay untouched
+        assert_eq!(master.variants().nth(2).unwrap().uri, "https://cdn.example.com/vod/1080p/index.m3u8?token=abc");
+
+        let mut playlist = Playlist::parse(MEDIA_ENCRYPTED).unwrap();
+        let Playlist::Media(media) = &mut playlist else {
+            panic!("expected a media playlist");
+        };
+        media.segments[3].tags.insert(0, Tag::Key(Key { attributes: AttributeList::parse(r#"METHOD=SAMPLE-AES,URI="skd://key65""#, 0).unwrap() }));
+        playlist.resolve_uris(&reqwest::Url::parse("https://v.example.com/a/b.m3u8").unwrap(), Some("/proxy?url="));
+        let Playlist::Media(media) = playlist else {
+            unreachable!();
+        };
+        assert_eq!(key_uri(media.key_for(1)), Some("/proxy?url=https%3A%2F%2Fkeys.example.com%2Fkey1.key"));
+        assert_eq!(key_uri(media.key_for(2)), Some("/proxy?url=https%3A%2F%2Fv.example.com%2Fa%2Fkey2.key"));
+        assert_eq!(media.segments[3].tags.first(), Some(&Tag::Key(Key { attributes: AttributeList::parse(r#"METHOD=SAMPLE-AES,URI="skd://key65""#, 0).unwrap() })));
+    }
+
+    #[test]
+    fn resolves_uris_of_unparsable_text() {
+        let base = reqwest::Url::parse("https://cdn.example.com/vod/index.m3u8").unwrap();
+        let text = "#EXTM3U\r\n#EXTINF:abc,\r\n#EXT-X-KEY:METHOD=AES-128,URI=\"key.key\",IV=0x1\r\nseg0.ts\r\n\r\n#EXT-X-MAP:URI=\"init.mp4\n";
+        assert_eq!(
+            resolve_uris_in_text(text, &base, Some("/proxy?url=")),
+            "#EXTM3U\n#EXTINF:abc,\n#EXT-X-KEY:METHOD=AES-128,URI=\"/proxy?url=https%3A%2F%2Fcdn.example.com%2Fvod%2Fkey.key\",IV=0x1\n\
+             /proxy?url=https%3A%2F%2Fcdn.example.com%2Fvod%2Fseg0.ts\n#EXT-X-MAP:URI=\"init.mp4\n"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_playlists() {
+        assert!(Playlist::parse("").is_err());
+        assert!(Playlist::parse("<html>not a playlist</html>").is_err());
+        assert!(Playlist::parse("#EXTM3U\n#EXTINF:abc,\nseg.ts\n").is_err());
+        assert!(Playlist::parse("#EXTM3U\n#EXT-X-KEY:METHOD=AES-128,URI=\"key\n").is_err());
+        assert!(Playlist::parse("#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=1\n").is_err());
+        assert!(Playlist::parse("#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=1\n#EXT-X-VERSION:3\nlow.m3u8\n").is_err());
+    }
+}
@@ -0,0 +1,284 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::vod::SearchResultItem;
+
+// --- Merging duplicates of aggregated search results ---
+//
+// Every source lists the same film under slightly different names: "三體 第一季", "三体（第1季）",
+// "三体". Titles are normalized (full-width forms, traditional characters, punctuation, season
+// suffixes) and grouped together with a compatible year and type, one entry per group.
+
+// Traditional characters common in titles, each followed by its simplified form
+const TRADITIONAL_PAIRS: &str = "萬万 與与 專专 業业 東东 絲丝 兩两 嚴严 個个 豐丰 臨临 為为 麗丽 舉举 義义 烏乌 樂乐 喬乔 \
+    習习 鄉乡 書书 買买 亂乱 爭争 於于 虧亏 雲云 亞亚 產产 親亲 億亿 僅仅 從从 倉仓 儀仪 們们 價价 眾众 優优 會会 傳传 \
+    傷伤 倫伦 偉伟 體体 來来 俠侠 侶侣 係系 倆俩 債债 傾倾 傑杰 偵侦 兒儿 黨党 內内 岡冈 寫写 軍军 農农 馮冯 沖冲 決决 \
+    況况 淨净 涼凉 減减 幾几 鳳凤 憑凭 凱凯 擊击 劃划 劉刘 則则 剛刚 創创 別别 劍剑 劇剧 勁劲 動动 務务 勝胜 勞劳 勢势 \
+    區区 醫医 華华 協协 單单 賣卖 盧卢 衛卫 卻却 廠厂 廳厅 歷历 壓压 縣县 參参 雙双 發发 變变 疊叠 葉叶 號号 嘆叹 嗎吗 \
+    啟启 吳吴 員员 響响 問问 喚唤 國国 圖图 圓圆 聖圣 場场 壞坏 塊块 堅坚 聲声 處处 備备 複复 夠够 頭头 夾夹 奪夺 奮奋 \
+    獎奖 婦妇 媽妈 嬌娇 孫孙 學学 寶宝 實实 寵宠 審审 憲宪 宮宫 寬宽 賓宾 將将 尋寻 對对 導导 層层 屬属 歲岁 島岛 嶺岭 \
+    峽峡 幣币 帥帅 師师 帶带 幫帮 幹干 庫库 應应 廟庙 廢废 開开 異异 張张 彈弹 強强 歸归 當当 錄录 徹彻 徑径 後后 復复 \
+    憶忆 懷怀 態态 戀恋 惡恶 悅悦 愛爱 慶庆 憂忧 戲戏 戰战 戶户 執执 擴扩 掃扫 揚扬 擾扰 搶抢 護护 報报 擔担 擁拥 擇择 \
+    揮挥 損损 換换 擺摆 攜携 攝摄 敵敌 數数 斬斩 斷断 無无 舊旧 時时 畫画 暢畅 曉晓 術术 機机 殺杀 雜杂 權权 條条 極极 \
+    構构 槍枪 楓枫 標标 樣样 檢检 樓楼 橫横 歡欢 歐欧 殘残 氣气 漢汉 湯汤 溝沟 沒没 滅灭 濤涛 淚泪 潔洁 灑洒 濃浓 濱滨 \
+    滿满 漁渔 溫温 灣湾 濕湿 滾滚 點点 煉炼 煙烟 爐炉 燈灯 燒烧 營营 熱热 燦灿 災灾 爺爷 牆墙 獨独 獄狱 獅狮 獵猎 獸兽 \
+    狀状 現现 環环 瑪玛 畢毕 療疗 盡尽 監监 盤盘 盜盗 礦矿 碼码 確确 禮礼 禍祸 離离 種种 積积 穩稳 窮穷 競竞 筆笔 築筑 \
+    簡简 類类 糧粮 緊紧 紅红 約约 級级 紀纪 純纯 紙纸 紗纱 練练 組组 細细 終终 經经 結结 絕绝 給给 統统 綠绿 維维 網网 \
+    緣缘 編编 線线 縱纵 總总 織织 繼继 續续 羅罗 聞闻 聯联 職职 聽听 腦脑 膽胆 臉脸 臺台 興兴 艦舰 藝艺 節节 範范 蘭兰 \
+    藥药 萊莱 蘇苏 蘿萝 蟲虫 蝦虾 補补 裝装 裡里 裏里 見见 規规 視视 覺觉 觀观 觸触 計计 討讨 讓让 記记 許许 設设 訪访 \
+    證证 評评 識识 詞词 試试 話话 詩诗 誠诚 語语 誤误 說说 請请 讀读 課课 誰谁 調调 談谈 謀谋 謎谜 謝谢 譯译 議议 諜谍 \
+    豬猪 貓猫 貝贝 負负 財财 貨货 質质 貴贵 費费 賀贺 資资 賊贼 賭赌 賞赏 賴赖 贏赢 趕赶 趙赵 躍跃 蹤踪 軌轨 軟软 輕轻 \
+    載载 輝辉 輪轮 轉转 轟轰 辦办 邊边 達达 過过 運运 還还 這这 進进 遠远 違违 連连 遲迟 適适 選选 遺遗 遙遥 鄭郑 醜丑 \
+    釋释 針针 銀银 鋼钢 錢钱 錯错 錦锦 鋒锋 鍾钟 鐘钟 鐵铁 鏡镜 鑰钥 鑽钻 長长 門门 閃闪 閉闭 閒闲 間间 閣阁 隊队 陽阳 \
+    陰阴 陣阵 階阶 際际 陸陆 陳陈 險险 隨随 隱隐 難难 雞鸡 電电 霧雾 靈灵 靜静 頂顶 項项 順顺 預预 領领 題题 顏颜 願愿 \
+    顧顾 顯显 風风 飛飞 飯饭 飄飘 餘余 館馆 馬马 駕驾 騎骑 驗验 驚惊 髮发 鬥斗 鬧闹 魚鱼 魯鲁 鮮鲜 鯨鲸 鳥鸟 鳴鸣 鴨鸭 \
+    鷹鹰 麥麦 黃黄 齊齐 齒齿 龍龙 龜龟 韓韩 夢梦 樹树 隻只 彎弯 殼壳 顆颗 壽寿 懸悬 煩烦 衝冲 憤愤 嶽岳";
+
+static TRADITIONAL_TO_SIMPLIFIED: Lazy<HashMap<char, char>> = Lazy::new(|| {
+    TRADITIONAL_PAIRS
+        .split_whitespace()
+        .filter_map(|pair| {
+            let mut chars = pair.chars();
+            Some((chars.next()?, chars.next()?))
+        })
+        .collect()
+});
+
+// Season suffixes of a normalized title: 第二季, 第2部, season2
+static SEASON_SUFFIX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?:第([0-9一二三四五六七八九十两]+)[季部]|season([0-9]+))$").unwrap());
+
+fn chinese_number(text: &str) -> Option<u32> {
+    if let Ok(number) = text.parse() {
+        return Some(number);
+    }
+    let digit = |c: char| "零一二三四五六七八九".chars().position(|d| d == c).map(|d| d as u32).or((c == '两').then_some(2));
+    match text.split_once('十') {
+        // 十, 十二, 二十, 二十三
+        Some((tens, ones)) => {
+            let tens = if tens.is_empty() { 1 } else { digit(tens.chars().next()?)? };
+            let ones = if ones.is_empty() { 0 } else { digit(ones.chars().next()?)? };
+            Some(tens * 10 + ones)
+        }
+        None if text.chars().count() == 1 => digit(text.chars().next()?),
+        None => None,
+    }
+}
+
+/// Title reduced to what identifies a show: lowercase, simplified, without punctuation or spaces,
+/// and with the season split off. Season 1 counts as no season, so "三体" matches "三体 第一季".
+pub(crate) fn normalize_title(title: &str) -> (String, Option<u32>) {
+    let normalized: String = title
+        .chars()
+        .map(|c| match c {
+            // Full-width ASCII forms, e.g. `（`, `２`, `Ａ`
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+            _ => *TRADITIONAL_TO_SIMPLIFIED.get(&c).unwrap_or(&c),
+        })
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect();
+    let Some(captures) = SEASON_SUFFIX.captures(&normalized) else {
+        return (normalized, None);
+    };
+    let season = captures.get(1).or_else(|| captures.get(2)).and_then(|number| chinese_number(number.as_str()));
+    let title = normalized[..captures.get(0).expect("whole match").start()].to_string();
+    if title.is_empty() {
+        return (normalized, None);
+    }
+    (title, season.filter(|season| *season > 1))
+}
+
+/// Broad kind of a video; `type_name` differs per source (国产剧, 大陆剧, 电视剧 …).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Category {
+    Movie,
+    Series,
+    Anime,
+    Variety,
+    Documentary,
+    Unknown,
+}
+
+impl Category {
+    fn of(type_name: &str) -> Self {
+        if type_name.contains("动漫") || type_name.contains("动画") {
+            Category::Anime
+        } else if type_name.contains("综艺") {
+            Category::Variety
+        } else if type_name.contains("纪录") {
+            Category::Documentary
+        } else if type_name.contains('剧') {
+            Category::Series
+        } else if type_name.contains('片') || type_name.contains("电影") {
+            Category::Movie
+        } else {
+            Category::Unknown
+        }
+    }
+
+    fn matches(self, other: Category) -> bool {
+        self == other || self == Category::Unknown || other == Category::Unknown
+    }
+}
+
+fn year(vod_year: &str) -> Option<u16> {
+    vod_year.get(..4).and_then(|year| year.parse().ok()).filter(|year| *year > 0)
+}
+
+/// One source's copy of a merged video, enough to fetch its detail or play it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct PlayOption {
+    source_code: Option<String>,
+    source_name: Option<String>,
+    api_url: Option<String>,
+    vod_id: String,
+    vod_remarks: String,
+    vod_play_from: String,
+    vod_play_url: String,
+}
+
+impl PlayOption {
+    fn of(item: &SearchResultItem) -> Self {
+        PlayOption {
+            source_code: item.source_code.clone(),
+            source_name: item.source_name.clone(),
+            api_url: item.api_url.clone(),
+            vod_id: item.vod_id.clone(),
+            vod_remarks: item.vod_remarks.clone(),
+            vod_play_from: item.vod_play_from.clone(),
+            vod_play_url: item.vod_play_url.clone(),
+        }
+    }
+}
+
+/// A video found in one or more sources. The fields of the first copy are shown, with gaps (cover,
+/// year, description …) filled from the others; `play_options` lists every copy in result order.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct MergedResult {
+    #[serde(flatten)]
+    pub(crate) item: SearchResultItem,
+    pub(crate) play_options: Vec<PlayOption>,
+}
+
+struct Group {
+    title: String,
+    season: Option<u32>,
+    year: Option<u16>,
+    category: Category,
+    merged: MergedResult,
+}
+
+impl Group {
+    // Unknown years and types match anything, since many sources leave them empty
+    fn accepts(&self, title: &str, season: Option<u32>, year: Option<u16>, category: Category) -> bool {
+        self.title == title
+            && self.season == season
+            && (self.year.is_none() || year.is_none() || self.year == year)
+            && self.category.matches(category)
+    }
+
+    fn add(&mut self, item: &SearchResultItem, year: Option<u16>, category: Category) {
+        let shown = &mut self.merged.item;
+        for (field, value) in [
+            (&mut shown.vod_pic, &item.vod_pic),
+            (&mut shown.vod_sub, &item.vod_sub),
+            (&mut shown.vod_year, &item.vod_year),
+            (&mut shown.vod_area, &item.vod_area),
+            (&mut shown.vod_director, &item.vod_director),
+            (&mut shown.vod_actor, &item.vod_actor),
+            (&mut shown.vod_content, &item.vod_content),
+        ] {
+            if field.is_empty() {
+                field.clone_from(value);
+            }
+        }
+        self.year = self.year.or(year);
+        if self.category == Category::Unknown {
+            self.category = category;
+        }
+        self.merged.play_options.push(PlayOption::of(item));
+    }
+}
+
+/// Groups copies of the same video from different sources, keeping the order of first appearance.
+pub(crate) fn merge_results(items: &[SearchResultItem]) -> Vec<MergedResult> {
+    let mut groups: Vec<Group> = Vec::new();
+    for item in items {
+        let (title, season) = normalize_title(&item.vod_name);
+        let year = year(&item.vod_year);
+        let category = Category::of(&item.type_name);
+        match groups.iter_mut().find(|group| group.accepts(&title, season, year, category)) {
+            Some(group) => group.add(item, year, category),
+            None => groups.push(Group {
+                title,
+                season,
+                year,
+                category,
+                merged: MergedResult { item: item.clone(), play_options: vec![PlayOption::of(item)] },
+            }),
+        }
+    }
+    groups.into_iter().map(|group| group.merged).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(source: &str, name: &str, year: &str, type_name: &str) -> SearchResultItem {
+        let mut item = SearchResultItem {
+            vod_id: format!("{}-id", source),
+            vod_name: name.to_string(),
+            vod_year: year.to_string(),
+            type_name: type_name.to_string(),
+            ..Default::default()
+        };
+        item.tag_source(source, source, None);
+        item
+    }
+
+    #[test]
+    fn normalizes_titles() {
+        assert_eq!(normalize_title("三體 第一季"), ("三体".to_string(), None));
+        assert_eq!(normalize_title("三体（第２季）"), ("三体".to_string(), Some(2)));
+        assert_eq!(normalize_title("狂飙 第十二部"), ("狂飙".to_string(), Some(12)));
+        assert_eq!(normalize_title("The Last of Us: Season 2"), ("thelastofus".to_string(), Some(2)));
+        assert_eq!(normalize_title("Friends・S01"), ("friendss01".to_string(), None));
+        // A title that is only a season suffix stays as it is
+        assert_eq!(normalize_title("第二季"), ("第二季".to_string(), None));
+    }
+
+    #[test]
+    fn traditional_table_has_valid_pairs() {
+        for pair in TRADITIONAL_PAIRS.split_whitespace() {
+            let chars: Vec<char> = pair.chars().collect();
+            assert!(chars.len() == 2 && chars[0] != chars[1], "bad pair {}", pair);
+        }
+    }
+
+    #[test]
+    fn merges_copies_across_sources() {
+        let mut with_cover = item("b", "三體", "", "国产剧");
+        with_cover.vod_pic = "https://img.example.com/santi.jpg".to_string();
+        let items = [
+            item("a", "三体", "2023", "大陆剧"),
+            with_cover,
+            item("c", "三体 第二季", "2025", "国产剧"),
+            item("d", "三体", "2023", "科幻片"),
+            item("e", "三体", "2008", "国产剧"),
+            item("f", "流浪地球", "2019", "科幻片"),
+        ];
+        let merged = merge_results(&items);
+        let sources: Vec<Vec<&str>> = merged
+            .iter()
+            .map(|result| result.play_options.iter().map(|option| option.source_code.as_deref().unwrap()).collect())
+            .collect();
+        assert_eq!(sources, [vec!["a", "b"], vec!["c"], vec!["d"], vec!["e"], vec!["f"]]);
+        assert_eq!(merged[0].item.vod_name, "三体");
+        assert_eq!(merged[0].item.vod_pic, "https://img.example.com/santi.jpg");
+        assert_eq!(merged[0].play_options[1].vod_id, "b-id");
+    }
+}
@@ -0,0 +1,201 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::http_client::HttpClient;
+use crate::search::{resolve_targets, search_request, SearchTarget};
+use crate::sources::SourceRegistry;
+use crate::vod::parse_vod_list;
+use crate::{send_http_request, HttpError, HttpRequestOptions};
+
+// Mirrors CUSTOM_API_CONFIG.testTimeout in public/js/config.js
+const PROBE_TIMEOUT_SECS: u64 = 5;
+const DEFAULT_MAX_AGE_SECS: u64 = 10 * 60;
+const MAX_CONCURRENT_PROBES: usize = 8;
+
+/// Result of probing one source with a lightweight search.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct SourceProbe {
+    source_code: String,
+    source_name: String,
+    api_url: Option<String>,
+    /// Answered with valid JSON and a successful API code.
+    healthy: bool,
+    status: Option<u16>,
+    latency_ms: u64,
+    valid_json: bool,
+    result_count: usize,
+    error: Option<HttpError>,
+    /// Unix time (seconds) of the probe.
+    checked_at: u64,
+    /// Served from the probe cache instead of a new request.
+    cached: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub(crate) struct ProbeOptions {
+    /// Ignore cached results.
+    pub(crate) force: bool,
+    /// How long cached results stay valid, 10 minutes by default.
+    pub(crate) max_age_secs: Option<u64>,
+    /// Search term to probe with; the default empty query lists the latest videos.
+    pub(crate) query: Option<String>,
+}
+
+/// Recent probe results, keyed by source code (or API URL for custom sources). Managed as Tauri state.
+#[derive(Default)]
+pub(crate) struct ProbeCache {
+    entries: Mutex<HashMap<String, (Instant, SourceProbe)>>,
+}
+
+impl ProbeCache {
+    fn get(&self, key: &str, max_age: Duration) -> Option<SourceProbe> {
+        let entries = self.entries.lock().unwrap();
+        let (probed_at, probe) = entries.get(key)?;
+        if probed_at.elapsed() > max_age {
+            return None;
+        }
+        Some(SourceProbe { cached: true, ..probe.clone() })
+    }
+
+    fn insert(&self, key: String, probe: SourceProbe) {
+        self.entries.lock().unwrap().insert(key, (Instant::now(), probe));
+    }
+
+    /// How dependable the last probe of `key` found the source, from 0 (failing) to 1 (healthy and
+    /// fast), however old that probe is. `None` if the source was never probed.
+    pub(crate) fn reliability(&self, key: &str) -> Option<f64> {
+        let entries = self.entries.lock().unwrap();
+        let (_, probe) = entries.get(key)?;
+        if !probe.healthy {
+            return Some(0.0);
+        }
+        // Answering within the probe timeout counts for at least half
+        Some(1.0 - 0.5 * (probe.latency_ms as f64 / (PROBE_TIMEOUT_SECS * 1000) as f64).min(1.0))
+    }
+}
+
+fn cache_key(target: &SearchTarget) -> String {
+    target.api_url.clone().unwrap_or_else(|| target.source_code.clone())
+}
+
+async fn probe_source(http: &HttpClient, target: &SearchTarget, query: &str) -> SourceProbe {
+    let started = Instant::now();
+    // Always ask the source itself, a cached answer says nothing about its health
+    let request = HttpRequestOptions { cache: None, ..search_request(&target.info, query, 1, PROBE_TIMEOUT_SECS) };
+    let response = send_http_request(http, request).await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    let mut probe = SourceProbe {
+        source_code: target.source_code.clone(),
+        source_name: target.source_name.clone(),
+        api_url: target.api_url.clone(),
+        healthy: false,
+        status: None,
+        latency_ms,
+        valid_json: false,
+        result_count: 0,
+        error: None,
+        checked_at: SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or_default(),
+        cached: false,
+    };
+    let response = match response {
+        Ok(response) => response,
+        Err(e) => {
+            probe.error = Some(e);
+            return probe;
+        }
+    };
+    probe.status = Some(response.status);
+    probe.valid_json = serde_json::from_str::<serde_json::Value>(&response.body).is_ok();
+    if !(200..300).contains(&response.status) {
+        return probe;
+    }
+    match parse_vod_list(&response.body) {
+        Ok(list) => {
+            probe.healthy = true;
+            probe.result_count = list.list.map_or(0, |items| items.len());
+        }
+        Err(e) => probe.error = Some(e),
+    }
+    probe
+}
+
+// Unregistered ids aren't probed or cached, they are reported as unhealthy
+fn unknown_source_probe(source_id: String) -> SourceProbe {
+    SourceProbe {
+        source_name: source_id.clone(),
+        error: Some(crate::sources::unknown_source(&source_id)),
+        source_code: source_id,
+        api_url: None,
+        healthy: false,
+        status: None,
+        latency_ms: 0,
+        valid_json: false,
+        result_count: 0,
+        checked_at: SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or_default(),
+        cached: false,
+    }
+}
+
+/// Checks whether sources respond, how fast, and with how many results. Without `source_ids` every
+/// registered source is probed; ids missing from the registry come back as unhealthy. Results are
+/// cached (see `ProbeOptions`) and sorted with healthy sources first, fastest first.
+#[tauri::command]
+pub(crate) async fn probe_sources(
+    source_ids: Option<Vec<String>>,
+    custom_api_urls: Option<Vec<String>>,
+    options: Option<ProbeOptions>,
+    registry: tauri::State<'_, SourceRegistry>,
+    http: tauri::State<'_, HttpClient>,
+    cache: tauri::State<'_, ProbeCache>,
+) -> Result<Vec<SourceProbe>, HttpError> {
+    let options = options.unwrap_or_default();
+    let source_ids = source_ids.unwrap_or_else(|| registry.list().into_iter().map(|entry| entry.id).collect());
+    let targets = resolve_targets(&registry, &source_ids, custom_api_urls);
+    let max_age = Duration::from_secs(options.max_age_secs.unwrap_or(DEFAULT_MAX_AGE_SECS));
+    let query = options.query.unwrap_or_default();
+
+    let mut probes = Vec::new();
+    let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_PROBES));
+    let mut tasks = JoinSet::new();
+    for target in targets {
+        let target = match target {
+            Ok(target) => target,
+            Err(source_id) => {
+                probes.push(unknown_source_probe(source_id));
+                continue;
+            }
+        };
+        if !options.force {
+            if let Some(probe) = cache.get(&cache_key(&target), max_age) {
+                probes.push(probe);
+                continue;
+            }
+        }
+        let semaphore = semaphore.clone();
+        let http = HttpClient::clone(&http);
+        let query = query.clone();
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await.expect("probe semaphore closed");
+            let probe = probe_source(&http, &target, &query).await;
+            (cache_key(&target), probe)
+        });
+    }
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok((key, probe)) => {
+                cache.insert(key, probe.clone());
+                probes.push(probe);
+            }
+            Err(e) => log::error!("[Rust] probe task failed to complete: {}", e),
+        }
+    }
+
+    probes.sort_by_key(|probe| (!probe.healthy, probe.latency_ms));
+    Ok(probes)
+}
@@ -0,0 +1,126 @@
+use crate::merge::normalize_title;
+use crate::vod::SearchResultItem;
+
+// Score weights; a title match always outranks year and source differences
+const EXACT_TITLE: f64 = 100.0;
+const EXACT_ALIAS: f64 = 80.0;
+const TITLE_PREFIX: f64 = 60.0;
+const TITLE_CONTAINS: f64 = 40.0;
+const ALIAS_CONTAINS: f64 = 30.0;
+const SAME_YEAR: f64 = 20.0;
+// Points lost per year of distance from the wanted year
+const YEAR_DECAY: f64 = 5.0;
+const RELIABILITY: f64 = 10.0;
+// Sources that haven't been probed yet sit between healthy and failing ones
+const UNKNOWN_RELIABILITY: f64 = 0.5;
+
+fn title_score(query: &(String, Option<u32>), title: &str, exact: f64, contains: f64, prefix: Option<f64>) -> f64 {
+    let candidate = normalize_title(title);
+    if candidate.0.is_empty() {
+        return 0.0;
+    }
+    if candidate == *query {
+        exact
+    } else if let (Some(prefix), true) = (prefix, candidate.0.starts_with(&query.0)) {
+        prefix
+    } else if candidate.0.contains(&query.0) {
+        contains
+    } else {
+        0.0
+    }
+}
+
+/// Relevance of `item` for `query`: exact title matches first, then `vod_sub` aliases, prefix and
+/// substring matches, plus closeness to `year` and the `reliability` (0–1) of the item's source.
+pub(crate) fn score(item: &SearchResultItem, query: &str, year: Option<u16>, reliability: Option<f64>) -> f64 {
+    let query = normalize_title(query);
+    let mut score = 0.0;
+    if !query.0.is_empty() {
+        let title = title_score(&query, &item.vod_name, EXACT_TITLE, TITLE_CONTAINS, Some(TITLE_PREFIX));
+        // vod_sub holds aliases separated by commas or slashes
+        let alias = item
+            .vod_sub
+            .split([',', '，', '/', '|', '、'])
+            .map(|alias| title_score(&query, alias, EXACT_ALIAS, ALIAS_CONTAINS, None))
+            .fold(0.0, f64::max);
+        score += title.max(alias);
+    }
+    let item_year = item.vod_year.get(..4).and_then(|year| year.parse::<u16>().ok());
+    if let (Some(wanted), Some(item_year)) = (year, item_year) {
+        score += (SAME_YEAR - YEAR_DECAY * f64::from(wanted.abs_diff(item_year))).max(0.0);
+    }
+    score + RELIABILITY * reliability.unwrap_or(UNKNOWN_RELIABILITY)
+}
+
+/// Sorts `items` by `score`, best first. Ties keep their order, so equal hits stay in source order.
+pub(crate) fn rank_results(
+    items: &mut [SearchResultItem],
+    query: &str,
+    year: Option<u16>,
+    reliability: impl Fn(&SearchResultItem) -> Option<f64>,
+) {
+    let mut scored: Vec<(f64, SearchResultItem)> = items
+        .iter_mut()
+        .map(|item| (score(item, query, year, reliability(item)), std::mem::take(item)))
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    for (slot, (_, item)) in items.iter_mut().zip(scored) {
+        *slot = item;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(name: &str, sub: &str, year: &str) -> SearchResultItem {
+        SearchResultItem {
+            vod_name: name.to_string(),
+            vod_sub: sub.to_string(),
+            vod_year: year.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn names(items: &[SearchResultItem]) -> Vec<&str> {
+        items.iter().map(|item| item.vod_name.as_str()).collect()
+    }
+
+    #[test]
+    fn ranks_exact_matches_before_prefix_and_substring_matches() {
+        let mut items = [
+            item("我的三体之章北海传", "", "2019"),
+            item("三体：地球往事", "", "2023"),
+            item("The Three-Body Problem", "三体, 三體", "2024"),
+            item("三體", "", "2023"),
+        ];
+        rank_results(&mut items, "三体", None, |_| None);
+        assert_eq!(names(&items), ["三體", "The Three-Body Problem", "三体：地球往事", "我的三体之章北海传"]);
+    }
+
+    #[test]
+    fn breaks_ties_by_year_and_source_reliability() {
+        let mut items = [item("三体", "", "2008"), item("三体", "", "2023"), item("三体", "", "2024")];
+        rank_results(&mut items, "三体", Some(2023), |_| None);
+        assert_eq!(items.iter().map(|item| item.vod_year.as_str()).collect::<Vec<_>>(), ["2023", "2024", "2008"]);
+
+        let mut items = [item("流浪地球", "", ""), item("流浪地球", "", "")];
+        items[0].source_code = Some("slow".to_string());
+        items[1].source_code = Some("healthy".to_string());
+        rank_results(&mut items, "流浪地球", None, |item| match item.source_code.as_deref() {
+            Some("healthy") => Some(1.0),
+            _ => Some(0.0),
+        });
+        assert_eq!(items[0].source_code.as_deref(), Some("healthy"));
+    }
+
+    #[test]
+    fn keeps_source_order_for_equal_scores() {
+        let mut items = [item("狂飙", "", ""), item("狂飙", "", ""), item("狂飙 第二季", "", "")];
+        items[0].vod_id = "first".to_string();
+        items[1].vod_id = "second".to_string();
+        rank_results(&mut items, "狂飙", None, |_| None);
+        let ids: Vec<&str> = items.iter().map(|item| item.vod_id.as_str()).collect();
+        assert_eq!(ids, ["first", "second", ""]);
+    }
+}
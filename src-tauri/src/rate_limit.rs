@@ -0,0 +1,394 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::http_client::{host_key, HttpClient};
+use crate::{HttpError, HttpErrorKind};
+
+// --- Per-host limits ---
+//
+// A search fans out into many pages per source, and some collection sites ban IPs that burst. Every
+// request of `send_http_request` first waits for a concurrency slot of its host, then for the host's
+// next start time under its requests-per-second limit; excess requests queue up in order.
+
+/// Limits for the requests to one host. Unset fields mean no limit.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(default)]
+pub(crate) struct HostLimits {
+    /// Requests in flight at the same time.
+    pub(crate) max_concurrent: Option<usize>,
+    /// Requests started per second.
+    pub(crate) requests_per_second: Option<f64>,
+}
+
+impl HostLimits {
+    /// Applies to hosts without limits of their own.
+    pub(crate) const DEFAULT: HostLimits = HostLimits { max_concurrent: Some(6), requests_per_second: Some(10.0) };
+
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.max_concurrent == Some(0) {
+            return Err("max_concurrent must be at least 1".to_string());
+        }
+        if let Some(rate) = self.requests_per_second {
+            if !(rate.is_finite() && rate > 0.0) {
+                return Err(format!("requests_per_second must be a positive number, got {}", rate));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl HostLimits {
+    /// The tighter of both limits in each field, for hosts shared by sources with different limits.
+    pub(crate) fn stricter(self, other: HostLimits) -> HostLimits {
+        fn min_of<T: PartialOrd>(a: Option<T>, b: Option<T>) -> Option<T> {
+            match (a, b) {
+                (Some(a), Some(b)) => Some(if b < a { b } else { a }),
+                (a, b) => a.or(b),
+            }
+        }
+        HostLimits {
+            max_concurrent: min_of(self.max_concurrent, other.max_concurrent),
+            requests_per_second: min_of(self.requests_per_second, other.requests_per_second),
+        }
+    }
+}
+
+struct HostState {
+    limits: HostLimits,
+    slots: Option<Arc<Semaphore>>,
+    next_start: Mutex<Instant>,
+}
+
+impl HostState {
+    /// Queue enforcing `limits`. Replacing `previous`, it keeps its concurrency slots if the
+    /// concurrency limit is the same and its schedule, so requests in flight still count.
+    fn new(limits: HostLimits, previous: Option<&HostState>) -> Self {
+        let slots = match previous {
+            Some(previous) if previous.limits.max_concurrent == limits.max_concurrent => previous.slots.clone(),
+            _ => limits.max_concurrent.map(|max| Arc::new(Semaphore::new(max))),
+        };
+        let next_start = previous.map_or_else(Instant::now, |previous| *previous.next_start.lock().unwrap());
+        HostState { limits, slots, next_start: Mutex::new(next_start) }
+    }
+
+    /// No request holds a slot or waits for one, and the rate schedule has caught up, so dropping
+    /// the queue and starting a new one later changes nothing.
+    fn is_idle(self: &Arc<Self>) -> bool {
+        let slots_free = match (&self.slots, self.limits.max_concurrent) {
+            (Some(slots), Some(max)) => slots.available_permits() >= max,
+            _ => true,
+        };
+        Arc::strong_count(self) == 1 && slots_free && *self.next_start.lock().unwrap() <= Instant::now()
+    }
+}
+
+/// Held while a request runs; frees the host's concurrency slots when dropped.
+pub(crate) struct HostPermit {
+    _slots: Vec<OwnedSemaphorePermit>,
+}
+
+// Waits for a concurrency slot of `state`, then for its next start time
+async fn wait_for(host: &str, state: &HostState) -> Option<OwnedSemaphorePermit> {
+    let slot = match &state.slots {
+        Some(slots) => Some(slots.clone().acquire_owned().await.expect("host semaphore closed")),
+        None => None,
+    };
+    if let Some(rate) = state.limits.requests_per_second {
+        let wait = {
+            let mut next_start = state.next_start.lock().unwrap();
+            let now = Instant::now();
+            let start = (*next_start).max(now);
+            *next_start = start + Duration::from_secs_f64(1.0 / rate);
+            start - now
+        };
+        if !wait.is_zero() {
+            log::debug!("[Rust] rate limit for {}: waiting {} ms", host, wait.as_millis());
+            tokio::time::sleep(wait).await;
+        }
+    }
+    slot
+}
+
+#[derive(Default)]
+struct Hosts {
+    /// Limits set through `set_rate_limits`; they win over source limits.
+    overrides: HashMap<String, HostLimits>,
+    /// Limits of source hosts, rebuilt from the registry.
+    sources: HashMap<String, HostLimits>,
+    /// Queues of the hosts with requests in flight or recently sent, each built for the limits it
+    /// enforces. Idle queues are dropped whenever a new one is added.
+    states: HashMap<String, Arc<HostState>>,
+    /// Extra queues for requests bringing limits of their own, by host and those limits.
+    request_states: HashMap<(String, String), Arc<HostState>>,
+}
+
+impl Hosts {
+    /// Limits of `host`: its own, or the default ones while it has none.
+    fn limits(&self, host: &str, default_limits: HostLimits) -> HostLimits {
+        self.overrides.get(host).or_else(|| self.sources.get(host)).copied().unwrap_or(default_limits)
+    }
+
+    /// Rebuilds the queues whose limits changed; requests already waiting keep the old ones.
+    fn refresh(&mut self, default_limits: HostLimits) {
+        let changed: Vec<(String, HostState)> = self
+            .states
+            .iter()
+            .map(|(host, state)| (host, state, self.limits(host, default_limits)))
+            .filter(|(_, state, limits)| state.limits != *limits)
+            .map(|(host, state, limits)| (host.clone(), HostState::new(limits, Some(state))))
+            .collect();
+        for (host, state) in changed {
+            self.states.insert(host, Arc::new(state));
+        }
+    }
+
+    // Keeps the maps from growing with every CDN host the HLS proxy ever touched
+    fn prune(&mut self) {
+        self.states.retain(|_, state| !state.is_idle());
+        self.request_states.retain(|_, state| !state.is_idle());
+    }
+}
+
+/// Per-host queues, shared through `HttpClient`.
+pub(crate) struct HostLimiter {
+    default_limits: Mutex<HostLimits>,
+    hosts: Mutex<Hosts>,
+}
+
+impl Default for HostLimiter {
+    fn default() -> Self {
+        HostLimiter { default_limits: Mutex::new(HostLimits::DEFAULT), hosts: Mutex::default() }
+    }
+}
+
+impl HostLimiter {
+    /// Sets the limits of `host` (as in a URL, with the port if it isn't the default one), over
+    /// any source limits. Requests already waiting keep the old limits.
+    pub(crate) fn configure(&self, host: &str, limits: HostLimits) {
+        let default_limits = *self.default_limits.lock().unwrap();
+        let mut hosts = self.hosts.lock().unwrap();
+        hosts.overrides.insert(host.to_string(), limits);
+        hosts.refresh(default_limits);
+    }
+
+    /// Replaces the limits of source hosts with `limits` (API and detail URLs of every source with
+    /// its limits). Sources sharing a host get the stricter of their limits; hosts no longer listed
+    /// fall back to the default.
+    pub(crate) fn set_source_limits<'a>(&self, limits: impl IntoIterator<Item = (&'a str, HostLimits)>) {
+        let mut sources: HashMap<String, HostLimits> = HashMap::new();
+        for (url, limits) in limits {
+            if let Some(host) = host_key(url) {
+                sources.entry(host).and_modify(|current| *current = current.stricter(limits)).or_insert(limits);
+            }
+        }
+        let default_limits = *self.default_limits.lock().unwrap();
+        let mut hosts = self.hosts.lock().unwrap();
+        hosts.sources = sources;
+        hosts.refresh(default_limits);
+    }
+
+    /// Changes the limits of hosts without their own.
+    pub(crate) fn set_default_limits(&self, limits: HostLimits) {
+        let mut default_limits = self.default_limits.lock().unwrap();
+        *default_limits = limits;
+        self.hosts.lock().unwrap().refresh(limits);
+    }
+
+    /// Queue of `host`, and the extra queue for `request_limits` when they differ from the host's.
+    fn states(&self, host: &str, request_limits: Option<HostLimits>) -> (Arc<HostState>, Option<Arc<HostState>>) {
+        let default_limits = *self.default_limits.lock().unwrap();
+        let mut hosts = self.hosts.lock().unwrap();
+        let limits = hosts.limits(host, default_limits);
+        let state = match hosts.states.get(host).cloned() {
+            Some(state) if state.limits == limits => state,
+            previous => {
+                if previous.is_none() {
+                    hosts.prune();
+                }
+                let state = Arc::new(HostState::new(limits, previous.as_deref()));
+                hosts.states.insert(host.to_string(), state.clone());
+                state
+            }
+        };
+        // Limits set through `set_rate_limits` win over the request's
+        let request_limits =
+            request_limits.filter(|request_limits| *request_limits != limits && !hosts.overrides.contains_key(host));
+        let request_state = request_limits.map(|request_limits| {
+            let key = (host.to_string(), format!("{:?}", request_limits));
+            if !hosts.request_states.contains_key(&key) {
+                hosts.prune();
+            }
+            hosts.request_states.entry(key).or_insert_with(|| Arc::new(HostState::new(request_limits, None))).clone()
+        });
+        (state, request_state)
+    }
+
+    /// Waits until a request to `url` may start. `limits` (e.g. from the request's source) apply to
+    /// this request on top of the host's limits without changing them, unless `set_rate_limits`
+    /// set limits for the host.
+    pub(crate) async fn acquire(&self, url: &str, limits: Option<HostLimits>) -> HostPermit {
+        let Some(host) = host_key(url) else {
+            return HostPermit { _slots: Vec::new() };
+        };
+        let (state, request_state) = self.states(&host, limits);
+        let mut slots = Vec::new();
+        for state in request_state.iter().chain([&state]) {
+            if let Some(slot) = wait_for(&host, state).await {
+                slots.push(slot);
+            }
+        }
+        HostPermit { _slots: slots }
+    }
+
+    fn settings(&self) -> RateLimitSettings {
+        let default_limits = *self.default_limits.lock().unwrap();
+        let hosts = self.hosts.lock().unwrap();
+        let names = hosts.states.keys().chain(hosts.sources.keys()).chain(hosts.overrides.keys());
+        RateLimitSettings {
+            default_limits,
+            hosts: names.map(|host| (host.clone(), hosts.limits(host, default_limits))).collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct RateLimitSettings {
+    /// Limits of hosts that haven't been configured.
+    default_limits: HostLimits,
+    /// Limits of every configured or source host and every host requested recently, from
+    /// `set_rate_limits`, sources or the defaults.
+    hosts: HashMap<String, HostLimits>,
+}
+
+/// Returns the default limits and the limits of every known host.
+#[tauri::command]
+pub(crate) fn get_rate_limits(http: tauri::State<'_, HttpClient>) -> RateLimitSettings {
+    http.limiter().settings()
+}
+
+/// Changes the limits for hosts without their own (`default_limits`) and/or of single hosts (`hosts`,
+/// keyed like `example.com` or `example.com:8080`). Host limits set here win over the sources'
+/// `ApiSourceInfo.rate_limit`.
+#[tauri::command]
+pub(crate) fn set_rate_limits(
+    default_limits: Option<HostLimits>,
+    hosts: Option<HashMap<String, HostLimits>>,
+    http: tauri::State<'_, HttpClient>,
+) -> Result<RateLimitSettings, HttpError> {
+    let hosts = hosts.unwrap_or_default();
+    for limits in default_limits.iter().chain(hosts.values()) {
+        limits.validate().map_err(|e| HttpError::new(HttpErrorKind::InvalidRequest, "Invalid rate limits", Some(e)))?;
+    }
+    let limiter = http.limiter();
+    if let Some(default_limits) = default_limits {
+        limiter.set_default_limits(default_limits);
+    }
+    for (host, limits) in hosts {
+        limiter.configure(&host.to_ascii_lowercase(), limits);
+    }
+    Ok(limiter.settings())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn limits_concurrency_per_host() {
+        let limiter = HostLimiter::default();
+        limiter.configure("a.example.com", HostLimits { max_concurrent: Some(2), requests_per_second: None });
+        let first = limiter.acquire("https://a.example.com/api.php?wd=1", None).await;
+        let _second = limiter.acquire("https://a.example.com/api.php?wd=2", None).await;
+
+        let third = limiter.acquire("https://a.example.com/api.php?wd=3", None);
+        assert!(tokio::time::timeout(Duration::from_millis(50), third).await.is_err());
+        // Other hosts have their own slots
+        let _other = limiter.acquire("https://b.example.com/api.php", None).await;
+
+        drop(first);
+        let third = limiter.acquire("https://a.example.com/api.php?wd=3", None);
+        assert!(tokio::time::timeout(Duration::from_millis(50), third).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn spaces_requests_by_rate() {
+        let limiter = HostLimiter::default();
+        let limits = HostLimits { max_concurrent: None, requests_per_second: Some(20.0) };
+        limiter.set_default_limits(HostLimits { max_concurrent: None, requests_per_second: None });
+        let started = Instant::now();
+        for _ in 0..4 {
+            limiter.acquire("https://a.example.com/", Some(limits)).await;
+        }
+        // First request at once, then one every 50 ms
+        assert!(started.elapsed() >= Duration::from_millis(150));
+    }
+
+    #[tokio::test]
+    async fn applies_request_limits_without_storing_them() {
+        let limiter = HostLimiter::default();
+        let unlimited = HostLimits { max_concurrent: None, requests_per_second: None };
+        limiter.set_default_limits(unlimited);
+        let single = HostLimits { max_concurrent: Some(1), requests_per_second: None };
+        let url = "https://shared.example.com/api.php";
+        let _a = limiter.acquire(url, Some(single)).await;
+
+        let second = limiter.acquire(url, Some(single));
+        assert!(tokio::time::timeout(Duration::from_millis(50), second).await.is_err());
+        // Requests without those limits only queue for the host's own
+        let other = limiter.acquire(url, None);
+        assert!(tokio::time::timeout(Duration::from_millis(50), other).await.is_ok());
+        assert_eq!(limiter.settings().hosts["shared.example.com"], unlimited);
+
+        // Overrides win over request limits
+        limiter.configure("shared.example.com", HostLimits { max_concurrent: Some(10), requests_per_second: None });
+        let overridden = limiter.acquire(url, Some(single));
+        assert!(tokio::time::timeout(Duration::from_millis(50), overridden).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn drops_queues_of_idle_hosts() {
+        let limiter = HostLimiter::default();
+        limiter.set_default_limits(HostLimits { max_concurrent: Some(2), requests_per_second: None });
+        let held = limiter.acquire("https://held.example.com/", None).await;
+        for index in 0..5 {
+            let _ = limiter.acquire(&format!("https://cdn{}.example.com/seg.ts", index), None).await;
+        }
+        let _new = limiter.acquire("https://new.example.com/", None).await;
+
+        let mut hosts: Vec<String> = limiter.settings().hosts.into_keys().collect();
+        hosts.sort();
+        assert_eq!(hosts, ["held.example.com", "new.example.com"]);
+        drop(held);
+    }
+
+    #[tokio::test]
+    async fn updates_default_limited_hosts_with_the_default() {
+        let limiter = HostLimiter::default();
+        let own = HostLimits { max_concurrent: Some(1), requests_per_second: None };
+        limiter.set_source_limits([("https://own.example.com", own)]);
+        let _ = limiter.acquire("https://own.example.com/", None).await;
+        let _ = limiter.acquire("https://other.example.com/", None).await;
+
+        let relaxed = HostLimits { max_concurrent: Some(20), requests_per_second: None };
+        limiter.set_default_limits(relaxed);
+        let settings = limiter.settings();
+        assert_eq!(settings.hosts["other.example.com"], relaxed);
+        assert_eq!(settings.hosts["own.example.com"], own);
+
+        // Dropped sources fall back to the default
+        let no_sources: [(&str, HostLimits); 0] = [];
+        limiter.set_source_limits(no_sources);
+        assert_eq!(limiter.settings().hosts["own.example.com"], relaxed);
+    }
+
+    #[test]
+    fn validates_limits() {
+        assert!(HostLimits::DEFAULT.validate().is_ok());
+        assert!(HostLimits { max_concurrent: Some(0), ..Default::default() }.validate().is_err());
+        assert!(HostLimits { requests_per_second: Some(0.0), ..Default::default() }.validate().is_err());
+        assert_eq!(host_key("http://a.example.com:8080/x").as_deref(), Some("a.example.com:8080"));
+    }
+}
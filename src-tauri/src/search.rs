@@ -0,0 +1,540 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use std::time::Instant;
+use tauri::Emitter;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::cancel::RequestGroups;
+use crate::http_cache::CacheCategory;
+use crate::merge::{merge_results, MergedResult};
+use crate::probe::ProbeCache;
+use crate::ranking::rank_results;
+use crate::sources::{ApiSourceInfo, SourceRegistry};
+use crate::vod::{self, parse_vod_list, SearchResultItem, VodListResponse};
+use crate::http_client::{HttpClient, BROWSER_USER_AGENT};
+use crate::{send_validated_request, HttpError, HttpErrorKind, HttpRequestOptions, API_PATH_DEFAULTS};
+
+// Mirrors AGGREGATED_SEARCH_CONFIG.timeout in public/js/config.js
+const AGGREGATED_SEARCH_TIMEOUT_SECS: u64 = 8;
+const DEFAULT_MAX_CONCURRENCY: usize = 6;
+
+// Pages of one source fetched at the same time in "all pages" mode
+const MAX_CONCURRENT_PAGES: usize = 4;
+
+/// Builds the GET request for one search page of `source`, with the headers of API_CONFIG.search.
+pub(crate) fn search_request(source: &ApiSourceInfo, query: &str, page: u32, timeout_secs: u64) -> HttpRequestOptions {
+    // Ensure query is URL encoded
+    let encoded_query = urlencoding::encode(query);
+    let full_url = match &source.search_path {
+        Some(search_path) if page > 1 => format!("{}{}{}&pg={}", source.api_base_url, search_path, encoded_query, page),
+        Some(search_path) => format!("{}{}{}", source.api_base_url, search_path, encoded_query),
+        None if page > 1 => format!(
+            "{}{}",
+            source.api_base_url,
+            API_PATH_DEFAULTS.search_page.replace("{query}", &encoded_query).replace("{page}", &page.to_string())
+        ),
+        None => format!("{}{}{}", source.api_base_url, API_PATH_DEFAULTS.search, encoded_query),
+    };
+
+    // Prepare headers - use defaults from API_CONFIG in JS for now
+    // Later, API_CONFIG.search.headers can also be moved to Rust
+    let mut headers = HashMap::new();
+    headers.insert("User-Agent".to_string(), BROWSER_USER_AGENT.to_string());
+    headers.insert("Accept".to_string(), "application/json".to_string());
+
+    HttpRequestOptions {
+        url: full_url,
+        method: Some("GET".to_string()),
+        headers: Some(headers),
+        body: None,
+        timeout_secs: Some(timeout_secs),
+        response_as_text: Some(true),
+        retry: None,
+        charset: source.charset.clone(),
+        cache: Some(CacheCategory::Search),
+        rate_limit: source.rate_limit,
+        proxy: source.proxy,
+    }
+}
+
+/// Optional knobs shared by the search commands, passed from JS as one `options` object.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub(crate) struct SearchOptions {
+    /// Single page to fetch (default 1); ignored with `all_pages`.
+    pub(crate) page: Option<u32>,
+    /// Follow `pagecount` of the first page and fetch the remaining pages too.
+    pub(crate) all_pages: bool,
+    /// Cap for `all_pages`, at most API_CONFIG.search.maxPages.
+    pub(crate) max_pages: Option<u32>,
+    /// Sources queried in parallel by `search_all_sources`.
+    pub(crate) max_concurrency: Option<usize>,
+    /// Also return `merged`, the results of `search_all_sources` with copies of the same video
+    /// from different sources grouped into one entry.
+    pub(crate) merge: bool,
+    /// Release year to prefer when ranking results by relevance.
+    pub(crate) year: Option<u16>,
+    /// Lets `cancel_requests` abort the search, e.g. when the user starts a new one.
+    pub(crate) group_id: Option<String>,
+    /// Emit each source's results from `search_all_sources` as soon as that source answers.
+    pub(crate) stream: bool,
+}
+
+/// Which search pages to fetch from a source.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum PageSelection {
+    Page(u32),
+    /// Follow `pagecount` of the first page, fetching at most `max_pages` pages in total.
+    All { max_pages: u32 },
+}
+
+impl PageSelection {
+    pub(crate) fn from_options(options: &SearchOptions) -> Self {
+        if options.all_pages {
+            let max_pages = options.max_pages.unwrap_or(API_PATH_DEFAULTS.max_search_pages);
+            PageSelection::All { max_pages: max_pages.clamp(1, API_PATH_DEFAULTS.max_search_pages) }
+        } else {
+            PageSelection::Page(options.page.unwrap_or(1).max(1))
+        }
+    }
+
+    pub(crate) fn first_page(self) -> u32 {
+        match self {
+            PageSelection::Page(page) => page,
+            PageSelection::All { .. } => 1,
+        }
+    }
+
+    /// Pages still to fetch once the first page reported `pagecount`; empty unless in `All` mode.
+    pub(crate) fn remaining_pages(self, pagecount: Option<i64>) -> RangeInclusive<u32> {
+        match self {
+            PageSelection::All { max_pages } => 2..=pagecount.unwrap_or(1).clamp(1, max_pages as i64) as u32,
+            PageSelection::Page(_) => 2..=1,
+        }
+    }
+}
+
+/// Combined result of one or more search pages of a source.
+#[derive(Debug, Default)]
+pub(crate) struct SearchPages {
+    pub(crate) msg: Option<String>,
+    pub(crate) pagecount: Option<i64>,
+    pub(crate) pages_fetched: u32,
+    pub(crate) list: Vec<SearchResultItem>,
+}
+
+/// Fetches the selected pages of `source`. In `All` mode the first page decides how many more pages
+/// exist; those are fetched concurrently and appended in page order. A failing later page is logged
+/// and skipped so one flaky page doesn't discard everything else.
+pub(crate) async fn fetch_search_pages(
+    http: &HttpClient,
+    source: &ApiSourceInfo,
+    query: &str,
+    timeout_secs: u64,
+    pages: PageSelection,
+) -> Result<SearchPages, HttpError> {
+    let first = fetch_search_page(http, source, query, pages.first_page(), timeout_secs).await?;
+    let mut result = SearchPages {
+        msg: first.msg,
+        pagecount: first.pagecount,
+        pages_fetched: 1,
+        list: first.list.unwrap_or_default(),
+    };
+
+    let remaining = pages.remaining_pages(first.pagecount);
+    if remaining.is_empty() {
+        return Ok(result);
+    }
+
+    let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_PAGES));
+    let mut tasks = JoinSet::new();
+    for page in remaining {
+        let semaphore = semaphore.clone();
+        let http = http.clone();
+        let source = source.clone();
+        let query = query.to_string();
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await.expect("search semaphore closed");
+            (page, fetch_search_page(&http, &source, &query, page, timeout_secs).await)
+        });
+    }
+
+    let mut later_pages = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok((page, Ok(response))) => later_pages.push((page, response.list.unwrap_or_default())),
+            Ok((page, Err(e))) => log::warn!("[Rust] search page {} of {} failed: {} {:?}", page, source.name, e.error, e.details),
+            Err(e) => log::error!("[Rust] search page task failed to complete: {}", e),
+        }
+    }
+    later_pages.sort_by_key(|(page, _)| *page);
+    for (_, items) in later_pages {
+        result.pages_fetched += 1;
+        result.list.extend(items);
+    }
+    Ok(result)
+}
+
+/// Fetches and parses one search page of `source`.
+pub(crate) async fn fetch_search_page(
+    http: &HttpClient,
+    source: &ApiSourceInfo,
+    query: &str,
+    page: u32,
+    timeout_secs: u64,
+) -> Result<VodListResponse, HttpError> {
+    let http_response = send_validated_request(http, search_request(source, query, page, timeout_secs), vod::is_list_response).await?;
+    if !(200..300).contains(&http_response.status) {
+        return Err(HttpError::new(
+            HttpErrorKind::Status(http_response.status),
+            format!("API request failed with status: {}", http_response.status),
+            Some(http_response.body),
+        ));
+    }
+    parse_vod_list(&http_response.body)
+}
+
+/// A source taking part in an aggregated search, resolved before the fan-out starts.
+pub(crate) struct SearchTarget {
+    pub(crate) source_code: String,
+    pub(crate) source_name: String,
+    pub(crate) api_url: Option<String>, // Only set for custom API URLs
+    pub(crate) info: ApiSourceInfo,
+}
+
+/// Looks up `source_ids` in the registry (skipping "custom") and appends one target per custom API URL.
+/// An id that isn't registered comes back as `Err` holding the id, so the remaining sources can
+/// still be searched and the caller reports that one as failed.
+pub(crate) fn resolve_targets(
+    registry: &SourceRegistry,
+    source_ids: &[String],
+    custom_api_urls: Option<Vec<String>>,
+) -> Vec<Result<SearchTarget, String>> {
+    let mut targets = Vec::new();
+    for source_id in source_ids.iter().filter(|id| id.as_str() != "custom") {
+        let Some(info) = registry.get(source_id) else {
+            targets.push(Err(source_id.clone()));
+            continue;
+        };
+        targets.push(Ok(SearchTarget {
+            source_code: source_id.clone(),
+            source_name: info.name.clone(),
+            api_url: None,
+            info,
+        }));
+    }
+    for (index, url) in custom_api_urls.unwrap_or_default().into_iter().enumerate() {
+        // Same naming as CUSTOM_API_CONFIG.namePrefix in the JS config
+        targets.push(Ok(SearchTarget {
+            source_code: "custom".to_string(),
+            source_name: format!("自定义{}", index + 1),
+            api_url: Some(url.clone()),
+            info: ApiSourceInfo::custom(url),
+        }));
+    }
+    targets
+}
+
+/// Outcome of one source within `search_all_sources`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct SourceSearchStatus {
+    source_code: String,
+    source_name: String,
+    api_url: Option<String>,
+    success: bool,
+    item_count: usize,
+    elapsed_ms: u64,
+    error: Option<HttpError>,
+}
+
+/// Tags the items of one source and sums up how its search went.
+fn source_outcome(
+    target: SearchTarget,
+    result: Result<Vec<SearchResultItem>, HttpError>,
+    elapsed_ms: u64,
+) -> (SourceSearchStatus, Vec<SearchResultItem>) {
+    let (items, error) = match result {
+        Ok(mut items) => {
+            for item in &mut items {
+                item.tag_source(&target.source_code, &target.source_name, target.api_url.as_deref());
+            }
+            (items, None)
+        }
+        Err(e) => {
+            log::warn!("[Rust] search in {} failed: {} {:?}", target.source_name, e.error, e.details);
+            (Vec::new(), Some(e))
+        }
+    };
+    let status = SourceSearchStatus {
+        source_code: target.source_code,
+        source_name: target.source_name,
+        api_url: target.api_url,
+        success: error.is_none(),
+        item_count: items.len(),
+        elapsed_ms,
+        error,
+    };
+    (status, items)
+}
+
+/// Status of a requested source id that isn't in the registry.
+fn unknown_source_status(source_id: String) -> SourceSearchStatus {
+    let error = crate::sources::unknown_source(&source_id);
+    log::warn!("[Rust] search skipped {}: {}", source_id, error.error);
+    SourceSearchStatus {
+        source_name: source_id.clone(),
+        source_code: source_id,
+        api_url: None,
+        success: false,
+        item_count: 0,
+        elapsed_ms: 0,
+        error: Some(error),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct AggregatedSearchResponse {
+    code: u16,
+    list: Vec<SearchResultItem>,
+    sources: Vec<SourceSearchStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    merged: Option<Vec<MergedResult>>,
+}
+
+// --- Streaming mode ---
+//
+// With `options.stream` every source is reported as soon as it answers, so the result grid can fill
+// progressively instead of waiting for the slowest source. Payloads carry `options.group_id` to tell
+// a current search from one that was replaced.
+
+/// Emitted once per source, in the order sources finish.
+pub(crate) const SOURCE_RESULT_EVENT: &str = "search-source-result";
+/// Emitted once the search ends, right before the command returns: after the last source, or with
+/// `cancelled` set when the search was cancelled or failed before ranking its results.
+pub(crate) const SEARCH_COMPLETE_EVENT: &str = "search-complete";
+
+#[derive(Serialize, Debug, Clone)]
+struct SourceResultEvent<'a> {
+    group_id: Option<&'a str>,
+    #[serde(flatten)]
+    status: &'a SourceSearchStatus,
+    items: &'a [SearchResultItem],
+}
+
+#[derive(Serialize, Debug, Clone)]
+struct SearchCompleteEvent<'a> {
+    group_id: Option<&'a str>,
+    item_count: usize,
+    sources: &'a [SourceSearchStatus],
+    cancelled: bool,
+}
+
+/// Emits `SEARCH_COMPLETE_EVENT` however the search ends, so streaming listeners never wait for it
+/// forever. Dropped before `complete`, it reports a cancelled search.
+struct SearchCompleteGuard {
+    app: Option<tauri::AppHandle>,
+    group_id: Option<String>,
+}
+
+impl SearchCompleteGuard {
+    fn new(app: &tauri::AppHandle, options: &SearchOptions) -> Self {
+        SearchCompleteGuard { app: options.stream.then(|| app.clone()), group_id: options.group_id.clone() }
+    }
+
+    fn emit(&mut self, item_count: usize, sources: &[SourceSearchStatus], cancelled: bool) {
+        if let Some(app) = self.app.take() {
+            let event = SearchCompleteEvent { group_id: self.group_id.as_deref(), item_count, sources, cancelled };
+            emit_event(&app, SEARCH_COMPLETE_EVENT, event);
+        }
+    }
+
+    fn complete(mut self, item_count: usize, sources: &[SourceSearchStatus]) {
+        self.emit(item_count, sources, false);
+    }
+}
+
+impl Drop for SearchCompleteGuard {
+    fn drop(&mut self) {
+        self.emit(0, &[], true);
+    }
+}
+
+fn emit_event<S: Serialize + Clone>(app: &tauri::AppHandle, event: &str, payload: S) {
+    if let Err(e) = app.emit(event, payload) {
+        log::warn!("[Rust] failed to emit {}: {}", event, e);
+    }
+}
+
+/// Searches several sources at once, running at most `options.max_concurrency` sources in parallel
+/// (paging options work as in `search_videos`).
+/// Items are tagged with `source_code`/`source_name` (and `api_url` for custom APIs) like the JS handlers did,
+/// and ranked by relevance, using the last `probe_sources` results as source reliability.
+/// With `options.group_id` the whole fan-out can be aborted by `cancel_requests`; with `options.stream`
+/// results are also emitted per source as they arrive (see `SOURCE_RESULT_EVENT`).
+/// Source ids missing from the registry are reported as failed sources instead of failing the search.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)] // Tauri injects the managed state as arguments
+pub(crate) async fn search_all_sources(
+    query: String,
+    source_ids: Vec<String>,
+    custom_api_urls: Option<Vec<String>>,
+    options: Option<SearchOptions>,
+    app: tauri::AppHandle,
+    registry: tauri::State<'_, SourceRegistry>,
+    http: tauri::State<'_, HttpClient>,
+    probes: tauri::State<'_, ProbeCache>,
+    groups: tauri::State<'_, RequestGroups>,
+) -> Result<AggregatedSearchResponse, HttpError> {
+    let options = options.unwrap_or_default();
+    let complete = SearchCompleteGuard::new(&app, &options);
+    if query.trim().is_empty() {
+        return Err(HttpError::new(HttpErrorKind::InvalidRequest, "Missing search query", None));
+    }
+
+    let targets = resolve_targets(&registry, &source_ids, custom_api_urls);
+    if targets.is_empty() {
+        return Err(HttpError::new(HttpErrorKind::InvalidRequest, "No sources selected for search", None));
+    }
+
+    let pages = PageSelection::from_options(&options);
+    let semaphore = Arc::new(Semaphore::new(options.max_concurrency.unwrap_or(DEFAULT_MAX_CONCURRENCY).max(1)));
+    let (http, fan_out_query, fan_out_app) = (HttpClient::clone(&http), query.clone(), app.clone());
+    let (stream, group_id) = (options.stream, options.group_id.clone());
+    // Cancelling drops the JoinSet, which aborts the per-source tasks as well
+    let finished = groups
+        .run(options.group_id.clone(), async move {
+            let report = |status: &SourceSearchStatus, items: &[SearchResultItem]| {
+                if stream {
+                    let event = SourceResultEvent { group_id: group_id.as_deref(), status, items };
+                    emit_event(&fan_out_app, SOURCE_RESULT_EVENT, event);
+                }
+            };
+            let mut finished = Vec::new();
+            let mut tasks = JoinSet::new();
+            for (index, target) in targets.into_iter().enumerate() {
+                let target = match target {
+                    Ok(target) => target,
+                    Err(source_id) => {
+                        let status = unknown_source_status(source_id);
+                        report(&status, &[]);
+                        finished.push((index, status, Vec::new()));
+                        continue;
+                    }
+                };
+                let semaphore = semaphore.clone();
+                let http = http.clone();
+                let query = fan_out_query.clone();
+                tasks.spawn(async move {
+                    let _permit = semaphore.acquire_owned().await.expect("search semaphore closed");
+                    let started = Instant::now();
+                    let result = fetch_search_pages(&http, &target.info, &query, AGGREGATED_SEARCH_TIMEOUT_SECS, pages)
+                        .await
+                        .map(|response| response.list);
+                    (index, target, result, started.elapsed().as_millis() as u64)
+                });
+            }
+
+            while let Some(joined) = tasks.join_next().await {
+                match joined {
+                    Ok((index, target, result, elapsed_ms)) => {
+                        let (status, items) = source_outcome(target, result, elapsed_ms);
+                        report(&status, &items);
+                        finished.push((index, status, items));
+                    }
+                    Err(e) => log::error!("[Rust] search task failed to complete: {}", e),
+                }
+            }
+            // Keep the caller's source order regardless of which source answered first
+            finished.sort_by_key(|(index, ..)| *index);
+            Ok(finished)
+        })
+        .await?;
+
+    let mut list = Vec::new();
+    let mut sources = Vec::new();
+    for (_, status, items) in finished {
+        sources.push(status);
+        list.extend(items);
+    }
+
+    rank_results(&mut list, &query, options.year, |item| {
+        probes.reliability(item.api_url.as_deref().or(item.source_code.as_deref()).unwrap_or_default())
+    });
+    // Groups keep the order of their first item, so merged results are ranked as well
+    let merged = options.merge.then(|| merge_results(&list));
+    complete.complete(list.len(), &sources);
+    Ok(AggregatedSearchResponse { code: 200, list, sources, merged })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(page: Option<u32>, all_pages: bool, max_pages: Option<u32>) -> SearchOptions {
+        SearchOptions { page, all_pages, max_pages, ..Default::default() }
+    }
+
+    #[test]
+    fn selects_pages_from_options() {
+        let max = API_PATH_DEFAULTS.max_search_pages;
+        assert!(matches!(PageSelection::from_options(&SearchOptions::default()), PageSelection::Page(1)));
+        assert!(matches!(PageSelection::from_options(&options(Some(3), false, None)), PageSelection::Page(3)));
+        assert!(matches!(PageSelection::from_options(&options(Some(0), false, None)), PageSelection::Page(1)));
+        // `page` is ignored when fetching all pages
+        let all = PageSelection::from_options(&options(Some(3), true, None));
+        assert!(matches!(all, PageSelection::All { max_pages } if max_pages == max));
+        assert_eq!(all.first_page(), 1);
+        assert!(matches!(PageSelection::from_options(&options(None, true, Some(0))), PageSelection::All { max_pages: 1 }));
+        assert!(matches!(PageSelection::from_options(&options(None, true, Some(max + 10))), PageSelection::All { max_pages } if max_pages == max));
+    }
+
+    #[test]
+    fn limits_remaining_pages_to_pagecount_and_max_pages() {
+        let all = PageSelection::All { max_pages: 5 };
+        assert_eq!(all.remaining_pages(Some(3)), 2..=3);
+        assert_eq!(all.remaining_pages(Some(20)), 2..=5);
+        assert!(all.remaining_pages(Some(1)).is_empty());
+        assert!(all.remaining_pages(Some(-1)).is_empty());
+        assert!(all.remaining_pages(None).is_empty());
+        assert!(PageSelection::Page(2).remaining_pages(Some(20)).is_empty());
+    }
+
+    #[test]
+    fn builds_search_page_urls() {
+        let source = ApiSourceInfo::custom("https://api.example.com".to_string());
+        let url = |source: &ApiSourceInfo, page| search_request(source, "流浪 地球", page, 8).url;
+        assert_eq!(url(&source, 1), "https://api.example.com/api.php/provide/vod/?ac=videolist&wd=%E6%B5%81%E6%B5%AA%20%E5%9C%B0%E7%90%83");
+        assert_eq!(url(&source, 2), "https://api.example.com/api.php/provide/vod/?ac=videolist&wd=%E6%B5%81%E6%B5%AA%20%E5%9C%B0%E7%90%83&pg=2");
+
+        let custom_path = ApiSourceInfo { search_path: Some("/vodsearch?wd=".to_string()), ..source };
+        assert_eq!(url(&custom_path, 1), "https://api.example.com/vodsearch?wd=%E6%B5%81%E6%B5%AA%20%E5%9C%B0%E7%90%83");
+        assert_eq!(url(&custom_path, 3), "https://api.example.com/vodsearch?wd=%E6%B5%81%E6%B5%AA%20%E5%9C%B0%E7%90%83&pg=3");
+    }
+
+    #[test]
+    fn resolves_known_and_custom_sources_and_keeps_unknown_ids() {
+        let registry = SourceRegistry::new(None);
+        let source_ids = ["ruyi", "gone", "custom", "dyttzy"].map(str::to_string);
+        let targets = resolve_targets(&registry, &source_ids, Some(vec!["https://custom.example.com".to_string()]));
+
+        let codes: Vec<_> = targets
+            .iter()
+            .map(|target| match target {
+                Ok(target) => target.source_code.as_str(),
+                Err(source_id) => source_id.as_str(),
+            })
+            .collect();
+        assert_eq!(codes, ["ruyi", "gone", "dyttzy", "custom"]);
+        assert_eq!(targets[1].as_ref().err().map(String::as_str), Some("gone"));
+        let custom = targets[3].as_ref().unwrap();
+        assert_eq!(custom.source_name, "自定义1");
+        assert_eq!(custom.api_url.as_deref(), Some("https://custom.example.com"));
+
+        let status = unknown_source_status("gone".to_string());
+        assert!(!status.success);
+        assert_eq!(status.error.map(|e| e.kind), Some(HttpErrorKind::UnknownSource));
+    }
+}
@@ -0,0 +1,627 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use crate::html_detail::HtmlDetailRules;
+use crate::http_client::HttpClient;
+use crate::proxy::ProxyRoute;
+use crate::rate_limit::HostLimits;
+use crate::{HttpError, HttpErrorKind};
+
+// --- API Configuration Structures ---
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub(crate) enum ApiType {
+    #[default]
+    Json,
+    Html, // For sources where details are scraped from HTML
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub(crate) struct ApiSourceInfo {
+    pub(crate) api_base_url: String,
+    pub(crate) name: String,
+    pub(crate) detail_base_url: Option<String>, // For HTML detail pages or different detail API base
+    #[serde(default)]
+    pub(crate) api_type: ApiType, // To distinguish between JSON API and HTML scraping for details
+    pub(crate) search_path: Option<String>, // Specific search path if different from default
+    pub(crate) detail_path: Option<String>, // Specific detail path if different from default (for JSON APIs)
+    // Example: some sources might use /vodsearch instead of /api.php/provide/vod/...
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) html_rules: Option<HtmlDetailRules>, // Selectors/regexes for scraping HTML detail pages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) charset: Option<String>, // Forces the response encoding (e.g. "gbk") for mislabeled sources
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) rate_limit: Option<HostLimits>, // Concurrency/RPS limits for the source's host instead of the defaults
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) proxy: Option<ProxyRoute>, // "direct" or "proxy" instead of the proxy's default route
+}
+
+impl ApiSourceInfo {
+    /// Ad-hoc source for a user-supplied API URL. Custom sources use the default paths.
+    pub(crate) fn custom(api_base_url: String) -> Self {
+        ApiSourceInfo {
+            api_base_url,
+            name: "Custom".to_string(),
+            detail_base_url: None,
+            api_type: ApiType::Json, // Assume custom is JSON for search
+            search_path: None,
+            detail_path: None,
+            ..Default::default()
+        }
+    }
+}
+
+/// One entry of the source registry as stored in `sources.json` and returned to the frontend.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct SourceEntry {
+    pub(crate) id: String,
+    #[serde(flatten)]
+    pub(crate) info: ApiSourceInfo,
+}
+
+/// File name of the user-editable registry inside the app data dir.
+pub(crate) const SOURCES_FILE_NAME: &str = "sources.json";
+
+// Built-in sources, used to seed `sources.json` on first start and as fallback if it can't be read.
+fn builtin_sources() -> Vec<SourceEntry> {
+    let m = vec![
+        // Data from public/js/config.js API_SITES, adapted for Rust
+        // Note: Detail paths for HTML sources are usually part of detail_base_url construction
+        // Search paths and detail paths for JSON sources can use defaults or be overridden here
+
+        // Example entry (need to populate all from config.js)
+        ("dyttzy", ApiSourceInfo {
+            api_base_url: "http://caiji.dyttzyapi.com".to_string(),
+            name: "电影天堂资源".to_string(),
+            detail_base_url: Some("http://caiji.dyttzyapi.com".to_string()), // Assuming it's JSON, or specific HTML base
+            api_type: ApiType::Json, // Assuming JSON, adjust if it's HTML scraping for detail
+            search_path: None, // Uses default
+            detail_path: None, // Uses default
+            ..Default::default()
+        }),
+        ("ruyi", ApiSourceInfo {
+            api_base_url: "https://cj.rycjapi.com".to_string(),
+            name: "如意资源".to_string(),
+            detail_base_url: None,
+            api_type: ApiType::Json,
+            search_path: None, detail_path: None,
+            ..Default::default()
+        }),
+        ("bfzy", ApiSourceInfo {
+            api_base_url: "https://bfzyapi.com".to_string(),
+            name: "暴风资源".to_string(),
+            detail_base_url: None,
+            api_type: ApiType::Json,
+            search_path: None, detail_path: None,
+            ..Default::default()
+        }),
+        ("tyyszy", ApiSourceInfo {
+            api_base_url: "https://tyyszy.com".to_string(),
+            name: "天涯资源".to_string(),
+            detail_base_url: None,
+            api_type: ApiType::Json,
+            search_path: None, detail_path: None,
+            ..Default::default()
+        }),
+        ("xiaomaomi", ApiSourceInfo {
+            api_base_url: "https://zy.xiaomaomi.cc".to_string(),
+            name: "小猫咪资源".to_string(),
+            detail_base_url: None,
+            api_type: ApiType::Json,
+            search_path: None, detail_path: None,
+            ..Default::default()
+        }),
+        ("ffzy", ApiSourceInfo { // ffzy has HTML detail
+            api_base_url: "http://ffzy5.tv".to_string(),
+            name: "非凡影视".to_string(),
+            detail_base_url: Some("http://ffzy5.tv".to_string()), // Base for HTML detail
+            api_type: ApiType::Html, // Detail is HTML
+            search_path: None, // JSON search
+            detail_path: Some("/index.php/vod/detail/id/{id}.html".to_string()), // Path template for HTML detail
+            html_rules: Some(HtmlDetailRules {
+                // ffzy pages also list share links and ads, only take its dated m3u8 paths
+                episode_pattern: Some(r#"\$(https?://[^"'\s]+?/\d{8}/\d+_[a-f0-9]+/index\.m3u8)"#.to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }),
+        ("heimuer", ApiSourceInfo { // heimuer has HTML detail
+            api_base_url: "https://json.heimuer.xyz".to_string(),
+            name: "黑木耳".to_string(),
+            detail_base_url: Some("https://heimuer.tv".to_string()), // Base for HTML detail
+            api_type: ApiType::Html, // Detail is HTML
+            search_path: None, // JSON search
+            detail_path: Some("/index.php/vod/detail/id/{id}.html".to_string()), // Path template for HTML detail
+            ..Default::default()
+        }),
+        ("zy360", ApiSourceInfo {
+            api_base_url: "https://360zy.com".to_string(),
+            name: "360资源".to_string(),
+            detail_base_url: None,
+            api_type: ApiType::Json,
+            search_path: None, detail_path: None,
+            ..Default::default()
+        }),
+        ("wolong", ApiSourceInfo {
+            api_base_url: "https://wolongzyw.com".to_string(),
+            name: "卧龙资源".to_string(),
+            detail_base_url: None,
+            api_type: ApiType::Json,
+            search_path: None, detail_path: None,
+            ..Default::default()
+        }),
+        ("hwba", ApiSourceInfo {
+            api_base_url: "https://cjhwba.com".to_string(),
+            name: "华为吧资源".to_string(),
+            detail_base_url: None,
+            api_type: ApiType::Json,
+            search_path: None, detail_path: None,
+            ..Default::default()
+        }),
+        ("jisu", ApiSourceInfo {
+            api_base_url: "https://jszyapi.com".to_string(),
+            name: "极速资源".to_string(),
+            detail_base_url: Some("https://jszyapi.com".to_string()),
+            api_type: ApiType::Json, // Assuming JSON, adjust if HTML
+            search_path: None, detail_path: None,
+            ..Default::default()
+        }),
+        ("dbzy", ApiSourceInfo {
+            api_base_url: "https://dbzy.com".to_string(),
+            name: "豆瓣资源".to_string(),
+            detail_base_url: None,
+            api_type: ApiType::Json,
+            search_path: None, detail_path: None,
+            ..Default::default()
+        }),
+        ("mozhua", ApiSourceInfo {
+            api_base_url: "https://mozhuazy.com".to_string(),
+            name: "魔爪资源".to_string(),
+            detail_base_url: None,
+            api_type: ApiType::Json,
+            search_path: None, detail_path: None,
+            ..Default::default()
+        }),
+        ("mdzy", ApiSourceInfo {
+            api_base_url: "https://www.mdzyapi.com".to_string(),
+            name: "魔都资源".to_string(),
+            detail_base_url: None,
+            api_type: ApiType::Json,
+            search_path: None, detail_path: None,
+            ..Default::default()
+        }),
+        ("zuid", ApiSourceInfo {
+            api_base_url: "https://api.zuidapi.com".to_string(),
+            name: "最大资源".to_string(),
+            detail_base_url: None,
+            api_type: ApiType::Json,
+            search_path: None, detail_path: None,
+            ..Default::default()
+        }),
+        ("yinghua", ApiSourceInfo {
+            api_base_url: "https://m3u8.apiyhzy.com".to_string(),
+            name: "樱花资源".to_string(),
+            detail_base_url: None,
+            api_type: ApiType::Json,
+            search_path: None, detail_path: None,
+            ..Default::default()
+        }),
+        ("baidu", ApiSourceInfo {
+            api_base_url: "https://api.apibdzy.com".to_string(),
+            name: "百度云资源".to_string(),
+            detail_base_url: None,
+            api_type: ApiType::Json,
+            search_path: None, detail_path: None,
+            ..Default::default()
+        }),
+        ("wujin", ApiSourceInfo {
+            api_base_url: "https://api.wujinapi.me".to_string(),
+            name: "无尽资源".to_string(),
+            detail_base_url: None,
+            api_type: ApiType::Json,
+            search_path: None, detail_path: None,
+            ..Default::default()
+        }),
+        ("wwzy", ApiSourceInfo {
+            api_base_url: "https://wwzy.tv".to_string(),
+            name: "旺旺短剧".to_string(),
+            detail_base_url: None, // Assuming JSON, adjust if HTML
+            api_type: ApiType::Json,
+            search_path: None, detail_path: None,
+            ..Default::default()
+        }),
+        ("ikun", ApiSourceInfo {
+            api_base_url: "https://ikunzyapi.com".to_string(),
+            name: "iKun资源".to_string(),
+            detail_base_url: None,
+            api_type: ApiType::Json,
+            search_path: None, detail_path: None,
+            ..Default::default()
+        }),
+        // Add other sources from config.js here...
+    ];
+    m.into_iter()
+        .map(|(id, info)| SourceEntry { id: id.to_string(), info })
+        .collect()
+}
+
+// --- End API Configuration Structures ---
+
+fn invalid_config(details: String) -> HttpError {
+    HttpError::new(HttpErrorKind::InvalidConfig, "Invalid source configuration", Some(details))
+}
+
+fn validate_source_id(id: &str) -> Result<(), HttpError> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        return Err(invalid_config(format!("Invalid source id '{}': use letters, digits, '_' or '-'", id)));
+    }
+    if id == "custom" {
+        // "custom" is reserved for ad-hoc API URLs passed by the frontend
+        return Err(invalid_config("Source id 'custom' is reserved".to_string()));
+    }
+    Ok(())
+}
+
+fn validate_http_url(field: &str, url: &str) -> Result<(), HttpError> {
+    match reqwest::Url::parse(url) {
+        Ok(parsed) if parsed.scheme() == "http" || parsed.scheme() == "https" => Ok(()),
+        _ => Err(invalid_config(format!("{} must be an absolute http(s) URL, got '{}'", field, url))),
+    }
+}
+
+fn validate_source(entry: &SourceEntry) -> Result<(), HttpError> {
+    validate_source_id(&entry.id)?;
+    let info = &entry.info;
+    if info.name.trim().is_empty() {
+        return Err(invalid_config(format!("Source '{}' has an empty name", entry.id)));
+    }
+    validate_http_url("api_base_url", &info.api_base_url)?;
+    if let Some(detail_base_url) = &info.detail_base_url {
+        validate_http_url("detail_base_url", detail_base_url)?;
+    }
+    if let Some(rules) = &info.html_rules {
+        rules.validate()?;
+    }
+    if let Some(charset) = &info.charset {
+        if !crate::charset::is_known_label(charset) {
+            return Err(invalid_config(format!("Source '{}' has an unknown charset '{}'", entry.id, charset)));
+        }
+    }
+    if let Some(limits) = &info.rate_limit {
+        limits.validate().map_err(|e| invalid_config(format!("Source '{}' has invalid rate limits: {}", entry.id, e)))?;
+    }
+    if info.api_type == ApiType::Html {
+        match &info.detail_path {
+            Some(path) if path.contains("{id}") => {}
+            _ => {
+                return Err(invalid_config(format!(
+                    "Source '{}' is an HTML source and needs a detail_path containing '{{id}}'",
+                    entry.id
+                )))
+            }
+        }
+    }
+    Ok(())
+}
+
+fn validate_sources(entries: &[SourceEntry]) -> Result<(), HttpError> {
+    for (index, entry) in entries.iter().enumerate() {
+        validate_source(entry)?;
+        if entries[..index].iter().any(|other| other.id == entry.id) {
+            return Err(invalid_config(format!("Duplicate source id '{}'", entry.id)));
+        }
+    }
+    Ok(())
+}
+
+/// Registry of collection sites, seeded from the built-in list and backed by `sources.json`.
+///
+/// Managed as Tauri state; every mutation is validated and written back to disk immediately.
+/// Clones share the same sources, so the commands can hand the file I/O to the blocking pool.
+#[derive(Clone)]
+pub(crate) struct SourceRegistry {
+    path: Option<PathBuf>,
+    sources: Arc<RwLock<Vec<SourceEntry>>>,
+}
+
+impl SourceRegistry {
+    pub(crate) fn new(path: Option<PathBuf>) -> Self {
+        SourceRegistry {
+            path,
+            sources: Arc::new(RwLock::new(builtin_sources())),
+        }
+    }
+
+    /// (Re)loads the registry from disk. A missing file is created from the built-in list;
+    /// an invalid file leaves the current sources untouched and returns the validation error.
+    pub(crate) fn load(&self) -> Result<usize, HttpError> {
+        let Some(path) = &self.path else {
+            return Ok(self.sources.read().unwrap().len());
+        };
+
+        if !path.exists() {
+            log::info!("[Rust] Source registry {} not found, seeding it with built-in sources", path.display());
+            let builtin = builtin_sources();
+            let count = builtin.len();
+            self.write_file(&builtin)?;
+            *self.sources.write().unwrap() = builtin;
+            return Ok(count);
+        }
+
+        let content = fs::read_to_string(path).map_err(|e| {
+            HttpError::new(HttpErrorKind::Io, "Failed to read source registry", Some(format!("{}: {}", path.display(), e)))
+        })?;
+        let entries: Vec<SourceEntry> = serde_json::from_str(&content)
+            .map_err(|e| invalid_config(format!("{}: {}", path.display(), e)))?;
+        validate_sources(&entries)?;
+
+        let count = entries.len();
+        *self.sources.write().unwrap() = entries;
+        log::info!("[Rust] Loaded {} sources from {}", count, path.display());
+        Ok(count)
+    }
+
+    pub(crate) fn get(&self, id: &str) -> Option<ApiSourceInfo> {
+        self.sources
+            .read()
+            .unwrap()
+            .iter()
+            .find(|entry| entry.id == id)
+            .map(|entry| entry.info.clone())
+    }
+
+    /// Looks up `source_id`, or builds an ad-hoc source when it is `"custom"`.
+    pub(crate) fn resolve(&self, source_id: &str, custom_api_url: Option<String>) -> Result<ApiSourceInfo, HttpError> {
+        if source_id == "custom" {
+            return match custom_api_url {
+                Some(url) => Ok(ApiSourceInfo::custom(url)),
+                None => Err(HttpError::new(HttpErrorKind::InvalidRequest, "Custom source selected but no API URL provided", None)),
+            };
+        }
+        self.get(source_id).ok_or_else(|| unknown_source(source_id))
+    }
+
+    pub(crate) fn list(&self) -> Vec<SourceEntry> {
+        self.sources.read().unwrap().clone()
+    }
+
+    pub(crate) fn add(&self, entry: SourceEntry) -> Result<(), HttpError> {
+        self.modify(|sources| {
+            if sources.iter().any(|existing| existing.id == entry.id) {
+                return Err(invalid_config(format!("Source '{}' already exists", entry.id)));
+            }
+            sources.push(entry);
+            Ok(())
+        })
+    }
+
+    pub(crate) fn update(&self, id: &str, info: ApiSourceInfo) -> Result<(), HttpError> {
+        self.modify(|sources| {
+            let existing = sources
+                .iter_mut()
+                .find(|existing| existing.id == id)
+                .ok_or_else(|| unknown_source(id))?;
+            existing.info = info;
+            Ok(())
+        })
+    }
+
+    pub(crate) fn remove(&self, id: &str) -> Result<(), HttpError> {
+        self.modify(|sources| {
+            let index = sources
+                .iter()
+                .position(|existing| existing.id == id)
+                .ok_or_else(|| unknown_source(id))?;
+            sources.remove(index);
+            Ok(())
+        })
+    }
+
+    // Applies `change` to a copy of the sources, validates and persists it, then swaps it in.
+    fn modify<F>(&self, change: F) -> Result<(), HttpError>
+    where
+        F: FnOnce(&mut Vec<SourceEntry>) -> Result<(), HttpError>,
+    {
+        let mut sources = self.sources.write().unwrap();
+        let mut updated = sources.clone();
+        change(&mut updated)?;
+        validate_sources(&updated)?;
+        self.write_file(&updated)?;
+        *sources = updated;
+        Ok(())
+    }
+
+    fn write_file(&self, entries: &[SourceEntry]) -> Result<(), HttpError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let write_error = |e: std::io::Error| {
+            HttpError::new(HttpErrorKind::Io, "Failed to write source registry", Some(format!("{}: {}", path.display(), e)))
+        };
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(write_error)?;
+        }
+        let json = serde_json::to_string_pretty(entries).map_err(|e| HttpError::new(HttpErrorKind::Io, "Failed to serialize source registry", Some(e.to_string())))?;
+        // Write to a temp file first so a crash never leaves a truncated registry behind
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, json).map_err(write_error)?;
+        fs::rename(&tmp_path, path).map_err(write_error)
+    }
+}
+
+pub(crate) fn unknown_source(id: &str) -> HttpError {
+    HttpError::new(HttpErrorKind::UnknownSource, format!("Unknown source_id: {}", id), None)
+}
+
+// --- Source registry commands ---
+
+#[tauri::command]
+pub(crate) fn list_sources(registry: tauri::State<'_, SourceRegistry>) -> Vec<SourceEntry> {
+    registry.list()
+}
+
+#[tauri::command]
+pub(crate) async fn add_source(
+    entry: SourceEntry,
+    registry: tauri::State<'_, SourceRegistry>,
+    http: tauri::State<'_, HttpClient>,
+) -> Result<(), HttpError> {
+    change_registry(&registry, &http, move |registry| registry.add(entry)).await
+}
+
+#[tauri::command]
+pub(crate) async fn update_source(
+    id: String,
+    source: ApiSourceInfo,
+    registry: tauri::State<'_, SourceRegistry>,
+    http: tauri::State<'_, HttpClient>,
+) -> Result<(), HttpError> {
+    change_registry(&registry, &http, move |registry| registry.update(&id, source)).await
+}
+
+#[tauri::command]
+pub(crate) async fn remove_source(
+    id: String,
+    registry: tauri::State<'_, SourceRegistry>,
+    http: tauri::State<'_, HttpClient>,
+) -> Result<(), HttpError> {
+    change_registry(&registry, &http, move |registry| registry.remove(&id)).await
+}
+
+/// Re-reads `sources.json`, e.g. after the user edited it by hand. Returns the number of sources.
+#[tauri::command]
+pub(crate) async fn reload_sources(
+    registry: tauri::State<'_, SourceRegistry>,
+    http: tauri::State<'_, HttpClient>,
+) -> Result<usize, HttpError> {
+    change_registry(&registry, &http, SourceRegistry::load).await
+}
+
+// Runs `change` on the blocking pool, as it reads or writes `sources.json`, then applies the
+// resulting sources to the client
+async fn change_registry<T: Send + 'static>(
+    registry: &SourceRegistry,
+    http: &HttpClient,
+    change: impl FnOnce(&SourceRegistry) -> Result<T, HttpError> + Send + 'static,
+) -> Result<T, HttpError> {
+    let blocking_registry = registry.clone();
+    let result = tokio::task::spawn_blocking(move || change(&blocking_registry))
+        .await
+        .map_err(|e| HttpError::new(HttpErrorKind::Other, "Source registry task failed", Some(e.to_string())))??;
+    apply_host_settings(http, &registry.list());
+    Ok(result)
+}
+
+/// Hands the `rate_limit` and `proxy` of every source in `entries` (the whole registry) to the shared
+/// client, so requests the frontend sends to a source's host follow them even without
+/// `HttpRequestOptions.rate_limit`/`proxy`. Hosts of removed or changed sources lose their limits.
+pub(crate) fn apply_host_settings(http: &HttpClient, entries: &[SourceEntry]) {
+    http.limiter().set_source_limits(entries.iter().flat_map(|entry| {
+        let urls = std::iter::once(entry.info.api_base_url.as_str()).chain(entry.info.detail_base_url.as_deref());
+        urls.filter_map(move |url| Some((url, entry.info.rate_limit?)))
+    }));
+    http.proxies().set_source_routes(entries.iter().flat_map(|entry| {
+        let urls = std::iter::once(entry.info.api_base_url.as_str()).chain(entry.info.detail_base_url.as_deref());
+        urls.filter_map(move |url| Some((url, entry.info.proxy?)))
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, api_base_url: &str) -> SourceEntry {
+        SourceEntry {
+            id: id.to_string(),
+            info: ApiSourceInfo { api_base_url: api_base_url.to_string(), name: id.to_string(), ..Default::default() },
+        }
+    }
+
+    fn temp_registry(name: &str) -> (SourceRegistry, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("libretv-sources-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join(SOURCES_FILE_NAME);
+        (SourceRegistry::new(Some(path.clone())), path)
+    }
+
+    fn kind(result: Result<(), HttpError>) -> Option<HttpErrorKind> {
+        result.err().map(|e| e.kind)
+    }
+
+    #[test]
+    fn builtin_sources_are_valid() {
+        validate_sources(&builtin_sources()).unwrap();
+    }
+
+    #[test]
+    fn rejects_invalid_entries() {
+        let invalid = Some(HttpErrorKind::InvalidConfig);
+        assert_eq!(kind(validate_source(&entry("ok_id-2", "https://api.example.com"))), None);
+        assert_eq!(kind(validate_source(&entry("", "https://api.example.com"))), invalid);
+        assert_eq!(kind(validate_source(&entry("has space", "https://api.example.com"))), invalid);
+        assert_eq!(kind(validate_source(&entry("custom", "https://api.example.com"))), invalid);
+        assert_eq!(kind(validate_source(&entry("relative", "/api.php"))), invalid);
+        assert_eq!(kind(validate_source(&entry("ftp", "ftp://api.example.com"))), invalid);
+
+        let bad_detail = SourceEntry {
+            info: ApiSourceInfo { detail_base_url: Some("not a url".to_string()), ..entry("a", "https://a.example.com").info },
+            ..entry("a", "https://a.example.com")
+        };
+        assert_eq!(kind(validate_source(&bad_detail)), invalid);
+        let bad_charset = SourceEntry {
+            info: ApiSourceInfo { charset: Some("no-such-charset".to_string()), ..entry("a", "https://a.example.com").info },
+            ..entry("a", "https://a.example.com")
+        };
+        assert_eq!(kind(validate_source(&bad_charset)), invalid);
+
+        let duplicates = [entry("a", "https://a.example.com"), entry("b", "https://b.example.com"), entry("a", "https://c.example.com")];
+        let error = validate_sources(&duplicates).unwrap_err();
+        assert!(error.error.contains("Duplicate source id 'a'"), "{}", error.error);
+    }
+
+    #[test]
+    fn html_sources_need_a_detail_path_with_id() {
+        let html = |detail_path: Option<&str>| SourceEntry {
+            info: ApiSourceInfo {
+                api_type: ApiType::Html,
+                detail_path: detail_path.map(str::to_string),
+                ..entry("html", "https://html.example.com").info
+            },
+            ..entry("html", "https://html.example.com")
+        };
+        assert_eq!(kind(validate_source(&html(Some("/vod/{id}.html")))), None);
+        assert_eq!(kind(validate_source(&html(Some("/vod/detail.html")))), Some(HttpErrorKind::InvalidConfig));
+        assert_eq!(kind(validate_source(&html(None))), Some(HttpErrorKind::InvalidConfig));
+    }
+
+    #[test]
+    fn seeds_saves_and_reloads_the_registry_file() {
+        let (registry, path) = temp_registry("round-trip");
+        let builtin = builtin_sources().len();
+        assert_eq!(registry.load().unwrap(), builtin);
+        assert!(path.exists());
+
+        registry.add(entry("mine", "https://mine.example.com")).unwrap();
+        assert_eq!(kind(registry.add(entry("mine", "https://other.example.com"))), Some(HttpErrorKind::InvalidConfig));
+        registry.update("mine", ApiSourceInfo { name: "Mine".to_string(), ..entry("mine", "https://mine.example.com/v2").info }).unwrap();
+        registry.remove("dyttzy").unwrap();
+        assert_eq!(kind(registry.remove("dyttzy")), Some(HttpErrorKind::UnknownSource));
+
+        let reloaded = SourceRegistry::new(Some(path.clone()));
+        assert_eq!(reloaded.load().unwrap(), builtin);
+        let mine = reloaded.get("mine").unwrap();
+        assert_eq!((mine.name.as_str(), mine.api_base_url.as_str()), ("Mine", "https://mine.example.com/v2"));
+        assert!(reloaded.get("dyttzy").is_none());
+
+        // A broken file is reported and leaves the loaded sources in place
+        fs::write(&path, r#"[{"id": "mine", "api_base_url": "/relative", "name": "Mine"}]"#).unwrap();
+        assert_eq!(kind(reloaded.load().map(|_| ())), Some(HttpErrorKind::InvalidConfig));
+        assert!(reloaded.get("mine").is_some());
+        assert_eq!(reloaded.list().len(), builtin);
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
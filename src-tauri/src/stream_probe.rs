@@ -0,0 +1,268 @@
+use reqwest::header::{REFERER, USER_AGENT};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::http_client::{HttpClient, BROWSER_USER_AGENT};
+use crate::m3u8::{MasterPlaylist, Playlist, Variant};
+use crate::{HttpError, HttpErrorKind};
+
+const DEFAULT_SEGMENTS: usize = 2;
+const DEFAULT_TIMEOUT_SECS: u64 = 10;
+// Parallel probes share the user's bandwidth, so only a few run at once
+const MAX_CONCURRENT_STREAMS: usize = 3;
+// Enough of a segment to measure throughput without downloading whole minutes of video
+const MAX_SEGMENT_BYTES: usize = 2 * 1024 * 1024;
+// Far above any real VOD playlist; a bigger answer is not a playlist and isn't read to the end
+const MAX_PLAYLIST_BYTES: usize = 2 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub(crate) struct StreamProbeOptions {
+    /// Segments downloaded per stream, 2 by default.
+    pub(crate) segments: Option<usize>,
+    /// Time budget for each stream, 10 seconds by default.
+    pub(crate) timeout_secs: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Resolution {
+    width: u32,
+    height: u32,
+}
+
+/// Measurements for one candidate URL.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct StreamProbe {
+    url: String,
+    ok: bool,
+    /// Time to first byte of the playlist the player loads first.
+    playlist_ttfb_ms: Option<u64>,
+    /// Average time to first byte of the probed segments.
+    segment_ttfb_ms: Option<u64>,
+    /// Download speed over the probed segments.
+    throughput_kbps: Option<u64>,
+    /// From the chosen variant of a master playlist.
+    resolution: Option<Resolution>,
+    bandwidth: Option<u64>,
+    segments_probed: usize,
+    error: Option<HttpError>,
+}
+
+impl StreamProbe {
+    fn new(url: String) -> Self {
+        StreamProbe {
+            url,
+            ok: false,
+            playlist_ttfb_ms: None,
+            segment_ttfb_ms: None,
+            throughput_kbps: None,
+            resolution: None,
+            bandwidth: None,
+            segments_probed: 0,
+            error: None,
+        }
+    }
+}
+
+// The variant hls.js starts with on a good connection: highest resolution, then bandwidth
+fn best_variant(master: &MasterPlaylist) -> Option<&Variant> {
+    master.variants().max_by_key(|variant| {
+        (variant.resolution().map(|(width, height)| u64::from(width) * u64::from(height)), variant.bandwidth())
+    })
+}
+
+struct Download {
+    final_url: reqwest::Url,
+    ttfb: Duration,
+    elapsed: Duration,
+    bytes: Vec<u8>,
+}
+
+async fn download(http: &HttpClient, url: &reqwest::Url, referer: &str, max_bytes: usize) -> Result<Download, HttpError> {
+    let started = Instant::now();
+    let mut response = http
+        .client(url.as_str(), None)
+        .get(url.clone())
+        .header(USER_AGENT, BROWSER_USER_AGENT)
+        .header(REFERER, referer)
+        .send()
+        .await
+        .map_err(|e| HttpError::new(HttpErrorKind::from_reqwest(&e), "Stream request failed", Some(e.to_string())))?;
+    let ttfb = started.elapsed();
+    if !response.status().is_success() {
+        let status = response.status().as_u16();
+        return Err(HttpError::new(HttpErrorKind::Status(status), "Stream request failed", Some(format!("{} returned {}", url, status))));
+    }
+    let final_url = response.url().clone();
+    let mut bytes = Vec::new();
+    while bytes.len() < max_bytes {
+        match response.chunk().await {
+            Ok(Some(chunk)) => bytes.extend_from_slice(&chunk),
+            Ok(None) => break,
+            Err(e) => return Err(HttpError::new(HttpErrorKind::from_reqwest(&e), "Stream download failed", Some(e.to_string()))),
+        }
+    }
+    Ok(Download { final_url, ttfb, elapsed: started.elapsed(), bytes })
+}
+
+fn parse_playlist(download: &Download) -> Result<Playlist, HttpError> {
+    if download.bytes.len() > MAX_PLAYLIST_BYTES {
+        return Err(HttpError::new(
+            HttpErrorKind::Decode,
+            "Playlist too large",
+            Some(format!("{} is over {} bytes", download.final_url, MAX_PLAYLIST_BYTES)),
+        ));
+    }
+    Playlist::parse(&String::from_utf8_lossy(&download.bytes))
+}
+
+// One byte over the cap is enough for parse_playlist to tell a cut-off playlist from a complete one
+async fn download_playlist(http: &HttpClient, url: &reqwest::Url, referer: &str) -> Result<Download, HttpError> {
+    download(http, url, referer, MAX_PLAYLIST_BYTES + 1).await
+}
+
+async fn probe_stream(http: &HttpClient, url: &str, segments: usize, probe: &mut StreamProbe) -> Result<(), HttpError> {
+    let url = reqwest::Url::parse(url).map_err(|e| HttpError::new(HttpErrorKind::InvalidRequest, "Invalid stream URL", Some(e.to_string())))?;
+    // Same default Referer as the libretv:// protocol, so results match what playback sees
+    let referer = format!("{}/", url.origin().ascii_serialization());
+
+    let first = download_playlist(http, &url, &referer).await?;
+    probe.playlist_ttfb_ms = Some(first.ttfb.as_millis() as u64);
+    let (media, media_url) = match parse_playlist(&first)? {
+        Playlist::Media(media) => (media, first.final_url),
+        Playlist::Master(master) => {
+            let variant = best_variant(&master)
+                .ok_or_else(|| HttpError::new(HttpErrorKind::Decode, "Master playlist has no variants", Some(url.to_string())))?;
+            probe.resolution = variant.resolution().map(|(width, height)| Resolution { width, height });
+            probe.bandwidth = variant.bandwidth();
+            let variant_url = first
+                .final_url
+                .join(&variant.uri)
+                .map_err(|e| HttpError::new(HttpErrorKind::Decode, "Invalid variant URI", Some(e.to_string())))?;
+            let download = download_playlist(http, &variant_url, &referer).await?;
+            match parse_playlist(&download)? {
+                Playlist::Media(media) => (media, download.final_url),
+                Playlist::Master(_) => return Err(HttpError::new(HttpErrorKind::Decode, "Nested master playlist", Some(variant_url.to_string()))),
+            }
+        }
+    };
+
+    let mut total_bytes = 0;
+    let mut total_time = Duration::ZERO;
+    let mut total_ttfb = Duration::ZERO;
+    for segment in media.segments.iter().take(segments) {
+        let segment_url = media_url
+            .join(&segment.uri)
+            .map_err(|e| HttpError::new(HttpErrorKind::Decode, "Invalid segment URI", Some(e.to_string())))?;
+        let download = download(http, &segment_url, &referer, MAX_SEGMENT_BYTES).await?;
+        total_bytes += download.bytes.len();
+        total_time += download.elapsed;
+        total_ttfb += download.ttfb;
+        probe.segments_probed += 1;
+    }
+    if probe.segments_probed == 0 {
+        return Err(HttpError::new(HttpErrorKind::Decode, "Playlist has no segments", Some(media_url.to_string())));
+    }
+    probe.segment_ttfb_ms = Some((total_ttfb / probe.segments_probed as u32).as_millis() as u64);
+    probe.throughput_kbps = Some((total_bytes as f64 * 8.0 / 1000.0 / total_time.as_secs_f64().max(0.001)) as u64);
+    Ok(())
+}
+
+// Working streams first, fastest download first, quicker start as tie breaker
+fn rank(probes: &mut [StreamProbe]) {
+    probes.sort_by_key(|probe| {
+        (!probe.ok, std::cmp::Reverse(probe.throughput_kbps.unwrap_or(0)), probe.playlist_ttfb_ms.unwrap_or(u64::MAX))
+    });
+}
+
+/// Downloads the playlist and first segments of each candidate m3u8 URL and returns them ranked by
+/// measured throughput, so the player can pick the fastest line.
+#[tauri::command]
+pub(crate) async fn probe_streams(
+    urls: Vec<String>,
+    options: Option<StreamProbeOptions>,
+    http: tauri::State<'_, HttpClient>,
+) -> Result<Vec<StreamProbe>, HttpError> {
+    let options = options.unwrap_or_default();
+    let segments = options.segments.unwrap_or(DEFAULT_SEGMENTS).max(1);
+    let timeout = Duration::from_secs(options.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS));
+
+    let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_STREAMS));
+    let mut tasks = JoinSet::new();
+    for url in urls {
+        let semaphore = semaphore.clone();
+        let http = HttpClient::clone(&http);
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await.expect("stream probe semaphore closed");
+            let mut probe = StreamProbe::new(url.clone());
+            let result = match tokio::time::timeout(timeout, probe_stream(&http, &url, segments, &mut probe)).await {
+                Ok(result) => result,
+                Err(_) => Err(HttpError::new(HttpErrorKind::Timeout, "Stream probe timed out", Some(url))),
+            };
+            match result {
+                Ok(()) => probe.ok = true,
+                Err(e) => probe.error = Some(e),
+            }
+            probe
+        });
+    }
+
+    let mut probes = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok(probe) => probes.push(probe),
+            Err(e) => log::error!("[Rust] stream probe task failed to complete: {}", e),
+        }
+    }
+    rank(&mut probes);
+    Ok(probes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_highest_resolution_variant() {
+        let Playlist::Master(master) = Playlist::parse(include_str!("../tests/fixtures/m3u8/master.m3u8")).unwrap() else {
+            panic!("expected a master playlist");
+        };
+        assert_eq!(best_variant(&master).unwrap().resolution(), Some((1920, 1080)));
+    }
+
+    #[test]
+    fn rejects_oversized_playlists() {
+        let download = |bytes: Vec<u8>| Download {
+            final_url: reqwest::Url::parse("https://cdn.example.com/index.m3u8").unwrap(),
+            ttfb: Duration::ZERO,
+            elapsed: Duration::ZERO,
+            bytes,
+        };
+        let master = include_bytes!("../tests/fixtures/m3u8/master.m3u8").to_vec();
+        assert!(parse_playlist(&download(master)).is_ok());
+        let error = parse_playlist(&download(vec![b'#'; MAX_PLAYLIST_BYTES + 1])).unwrap_err();
+        assert_eq!(error.error, "Playlist too large");
+    }
+
+    #[test]
+    fn ranks_working_streams_by_throughput() {
+        let probe = |url: &str, ok: bool, throughput: Option<u64>, ttfb: u64| StreamProbe {
+            ok,
+            throughput_kbps: throughput,
+            playlist_ttfb_ms: Some(ttfb),
+            ..StreamProbe::new(url.to_string())
+        };
+        let mut probes = vec![
+            probe("dead", false, None, 10),
+            probe("slow", true, Some(800), 50),
+            probe("fast", true, Some(9000), 400),
+            probe("fast-start", true, Some(9000), 100),
+        ];
+        rank(&mut probes);
+        let order: Vec<_> = probes.iter().map(|probe| probe.url.as_str()).collect();
+        assert_eq!(order, ["fast-start", "fast", "slow", "dead"]);
+    }
+}
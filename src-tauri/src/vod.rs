@@ -0,0 +1,517 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::{HttpError, HttpErrorKind, HttpResponse};
+
+// --- MacCMS `ac=videolist` payload ---
+//
+// Collection sites are inconsistent: ids and years come as strings or numbers, fields are
+// missing or null, and `code` is sometimes a string. Everything is deserialized leniently and
+// normalized to plain strings so the frontend always sees the same shape.
+
+fn lenient_string<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(s) => s.trim().to_string(),
+        other => other.to_string(),
+    })
+}
+
+fn lenient_number<'de, D>(deserializer: D) -> Result<Option<i64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::Number(n) => n.as_i64().or_else(|| n.as_f64().map(|f| f as i64)),
+        serde_json::Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    })
+}
+
+fn lenient_list<'de, D>(deserializer: D) -> Result<Option<Vec<SearchResultItem>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::Array(items) => Some(
+            items
+                .into_iter()
+                // Skip entries that aren't objects instead of failing the whole page
+                .filter_map(|item| serde_json::from_value(item).ok())
+                .collect(),
+        ),
+        _ => None,
+    })
+}
+
+/// One video of a MacCMS `list` array, as used by search results and detail responses.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub(crate) struct SearchResultItem {
+    #[serde(deserialize_with = "lenient_string")]
+    pub(crate) vod_id: String,
+    #[serde(deserialize_with = "lenient_string")]
+    pub(crate) vod_name: String,
+    #[serde(deserialize_with = "lenient_string")]
+    pub(crate) vod_sub: String, // Alias / subtitle, e.g. the original title
+    #[serde(deserialize_with = "lenient_string")]
+    pub(crate) vod_pic: String,
+    #[serde(deserialize_with = "lenient_string")]
+    pub(crate) type_id: String,
+    #[serde(deserialize_with = "lenient_string")]
+    pub(crate) type_name: String,
+    #[serde(deserialize_with = "lenient_string")]
+    pub(crate) vod_remarks: String,
+    #[serde(deserialize_with = "lenient_string")]
+    pub(crate) vod_year: String,
+    #[serde(deserialize_with = "lenient_string")]
+    pub(crate) vod_area: String,
+    #[serde(deserialize_with = "lenient_string")]
+    pub(crate) vod_lang: String,
+    #[serde(deserialize_with = "lenient_string")]
+    pub(crate) vod_director: String,
+    #[serde(deserialize_with = "lenient_string")]
+    pub(crate) vod_actor: String,
+    #[serde(deserialize_with = "lenient_string")]
+    pub(crate) vod_content: String,
+    #[serde(deserialize_with = "lenient_string")]
+    pub(crate) vod_time: String,
+    #[serde(deserialize_with = "lenient_string")]
+    pub(crate) vod_play_from: String,
+    #[serde(deserialize_with = "lenient_string")]
+    pub(crate) vod_play_url: String,
+    // Filled in by us, not by the API
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) source_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) source_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) api_url: Option<String>, // Specific custom API URL the item came from
+}
+
+impl SearchResultItem {
+    pub(crate) fn tag_source(&mut self, source_code: &str, source_name: &str, api_url: Option<&str>) {
+        self.source_code = Some(source_code.to_string());
+        self.source_name = Some(source_name.to_string());
+        self.api_url = api_url.map(str::to_string);
+    }
+}
+
+/// Raw `ac=videolist` response as returned by the collection site.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub(crate) struct VodListResponse {
+    #[serde(deserialize_with = "lenient_number")]
+    pub(crate) code: Option<i64>,
+    pub(crate) msg: Option<String>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub(crate) page: Option<i64>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub(crate) pagecount: Option<i64>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub(crate) total: Option<i64>,
+    #[serde(deserialize_with = "lenient_list")]
+    pub(crate) list: Option<Vec<SearchResultItem>>,
+}
+
+/// Parses a videolist body and applies the checks of the JS handlers:
+/// codes 0/1/200 are success, and code 1 may come without a list.
+pub(crate) fn parse_vod_list(body: &str) -> Result<VodListResponse, HttpError> {
+    let mut response: VodListResponse = serde_json::from_str(body).map_err(|e| HttpError::new(HttpErrorKind::Decode, "Invalid JSON in API response", Some(e.to_string())))?;
+
+    if let Some(code) = response.code {
+        if ![0, 1, 200].contains(&code) {
+            return Err(HttpError::new(HttpErrorKind::Api, format!("API returned error code: {}", code), response.msg));
+        }
+    }
+    if response.list.is_none() {
+        if response.code != Some(1) {
+            return Err(HttpError::new(HttpErrorKind::Api, "Invalid API response: missing list array", None));
+        }
+        response.list = Some(Vec::new());
+    }
+    Ok(response)
+}
+
+/// Whether a search response is a usable MacCMS list, i.e. worth caching.
+pub(crate) fn is_list_response(response: &HttpResponse) -> bool {
+    parse_vod_list(&response.body).is_ok()
+}
+
+/// Whether a detail response holds a video, i.e. worth caching.
+pub(crate) fn is_detail_response(response: &HttpResponse) -> bool {
+    parse_vod_list(&response.body).is_ok_and(|response| response.list.is_some_and(|list| !list.is_empty()))
+}
+
+// --- Video detail ---
+
+// Same as M3U8_PATTERN in public/js/config.js
+static M3U8_PATTERN: Lazy<Regex> = Lazy::new(|| Regex::new(r#"\$https?://[^"'\s]+?\.m3u8"#).unwrap());
+
+/// Metadata part of a detail response, keyed like the `videoInfo` object the JS handlers returned.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub(crate) struct VideoInfo {
+    pub(crate) title: String,
+    pub(crate) cover: String,
+    pub(crate) desc: String,
+    #[serde(rename = "type")]
+    pub(crate) type_name: String,
+    pub(crate) year: String,
+    pub(crate) area: String,
+    pub(crate) director: String,
+    pub(crate) actor: String,
+    pub(crate) remarks: String,
+    pub(crate) source_name: String,
+    pub(crate) source_code: String,
+}
+
+impl VideoInfo {
+    pub(crate) fn from_item(item: &SearchResultItem, source_code: &str, source_name: &str) -> Self {
+        VideoInfo {
+            title: item.vod_name.clone(),
+            cover: item.vod_pic.clone(),
+            desc: item.vod_content.clone(),
+            type_name: item.type_name.clone(),
+            year: item.vod_year.clone(),
+            area: item.vod_area.clone(),
+            director: item.vod_director.clone(),
+            actor: item.vod_actor.clone(),
+            remarks: item.vod_remarks.clone(),
+            source_name: source_name.to_string(),
+            source_code: source_code.to_string(),
+        }
+    }
+}
+
+/// One episode of a play line, from a `label$url` pair.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct Episode {
+    pub(crate) label: String,
+    pub(crate) url: String,
+}
+
+/// One play line (CDN / player) of a video, named after its `vod_play_from` entry.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct PlayLine {
+    pub(crate) name: String,
+    pub(crate) episodes: Vec<Episode>,
+}
+
+impl PlayLine {
+    pub(crate) fn urls(&self) -> Vec<String> {
+        self.episodes.iter().map(|episode| episode.url.clone()).collect()
+    }
+
+    /// Whether every episode links an HLS playlist rather than e.g. a share page of a cloud player.
+    pub(crate) fn is_m3u8(&self) -> bool {
+        self.episodes.iter().all(|episode| episode.url.split('?').next().unwrap_or_default().ends_with(".m3u8"))
+    }
+}
+
+/// Detail response, shaped like the result of `handleStandardDetailFetch` in apiDetailHandlers.js.
+/// `episodes` keeps the URLs of the first m3u8 line (or of the first line when none is); `play_lines`
+/// has every line so users can switch.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct VideoDetail {
+    pub(crate) code: u16,
+    pub(crate) episodes: Vec<String>,
+    pub(crate) play_lines: Vec<PlayLine>,
+    pub(crate) detail_url: String,
+    pub(crate) video_info: VideoInfo,
+}
+
+/// Splits `vod_play_url` into its play lines. Lines are separated by `$$$` (names in `vod_play_from`
+/// use the same separator), episodes by `#`, and each episode is `label$url`.
+/// Entries without an http(s) URL and lines without any episode are dropped.
+pub(crate) fn parse_play_lines(play_from: &str, play_url: &str) -> Vec<PlayLine> {
+    let names: Vec<&str> = play_from.split("$$$").map(str::trim).collect();
+
+    play_url
+        .split("$$$")
+        .enumerate()
+        .filter_map(|(line_index, line)| {
+            let episodes: Vec<Episode> = line
+                .split('#')
+                .filter_map(|episode| {
+                    // Some sources omit the label and only list the URL
+                    let (label, url) = match episode.split_once('$') {
+                        Some((label, url)) => (label.trim(), url.trim()),
+                        None => ("", episode.trim()),
+                    };
+                    (url.starts_with("http://") || url.starts_with("https://")).then_some((label, url))
+                })
+                // Numbered among the kept entries, so dropped ones leave no gaps
+                .enumerate()
+                .map(|(episode_index, (label, url))| {
+                    let label = if label.is_empty() { format!("第{}集", episode_index + 1) } else { label.to_string() };
+                    Episode { label, url: url.to_string() }
+                })
+                .collect();
+            if episodes.is_empty() {
+                return None;
+            }
+            let name = match names.get(line_index) {
+                Some(name) if !name.is_empty() => name.to_string(),
+                _ => format!("线路{}", line_index + 1),
+            };
+            Some(PlayLine { name, episodes })
+        })
+        .collect()
+}
+
+/// Detail of the first video in a JSON detail response. A response without any video is an
+/// `Api` error, also for code 1 with an empty list: the id doesn't exist (anymore) on the source.
+pub(crate) fn parse_video_detail(
+    body: &str,
+    source_code: &str,
+    source_name: &str,
+    detail_url: String,
+) -> Result<VideoDetail, HttpError> {
+    let response = parse_vod_list(body)?;
+    let Some(item) = response.list.unwrap_or_default().into_iter().next() else {
+        return Err(HttpError::new(
+            HttpErrorKind::Api,
+            "Video not found",
+            response.code.map(|code| format!("code: {}", code)),
+        ));
+    };
+
+    let play_lines = parse_play_lines(&item.vod_play_from, &item.vod_play_url);
+    let episodes = match play_lines.iter().find(|line| line.is_m3u8()).or(play_lines.first()) {
+        Some(line) => line.urls(),
+        None => episodes_from_content(&item.vod_content),
+    };
+    Ok(VideoDetail {
+        code: 200,
+        episodes,
+        play_lines,
+        detail_url,
+        video_info: VideoInfo::from_item(&item, source_code, source_name),
+    })
+}
+
+/// Fallback for sources that only put `$https://….m3u8` links into the description.
+pub(crate) fn episodes_from_content(content: &str) -> Vec<String> {
+    M3U8_PATTERN
+        .find_iter(content)
+        .map(|m| m.as_str().trim_start_matches('$').to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_numbers_and_strings_leniently() {
+        // Shapes seen from real MacCMS sites: numeric ids and years, string codes, nulls
+        let body = r#"{"code":"1","msg":"数据列表","page":"1","pagecount":3,"limit":"20","total":"58","list":[
+            {"vod_id":1024,"vod_name":" 流浪地球2 ","vod_sub":null,"vod_pic":"https://img.example.com/1024.jpg",
+             "type_id":6,"type_name":"科幻片","vod_year":2023,"vod_remarks":"HD","vod_time":"2023-04-01 10:00:00"},
+            {"vod_id":"1025","vod_name":"流浪地球","vod_year":"2019","vod_douban_score":7.9},
+            "not an object"
+        ]}"#;
+        let response = parse_vod_list(body).unwrap();
+        assert_eq!(response.code, Some(1));
+        assert_eq!(response.page, Some(1));
+        assert_eq!(response.pagecount, Some(3));
+        assert_eq!(response.total, Some(58));
+
+        let list = response.list.unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].vod_id, "1024");
+        assert_eq!(list[0].vod_name, "流浪地球2");
+        assert_eq!(list[0].vod_sub, "");
+        assert_eq!(list[0].type_id, "6");
+        assert_eq!(list[0].vod_year, "2023");
+        assert_eq!(list[1].vod_id, "1025");
+        assert_eq!(list[1].vod_year, "2019");
+        assert_eq!(list[1].vod_play_url, "");
+    }
+
+    #[test]
+    fn accepts_success_codes_and_null_lists() {
+        for code in ["0", "1", "200", "\"200\""] {
+            let body = format!(r#"{{"code":{},"list":[{{"vod_id":1,"vod_name":"a"}}]}}"#, code);
+            assert_eq!(parse_vod_list(&body).unwrap().list.unwrap().len(), 1, "code {}", code);
+        }
+        // Code 1 without results may come with a null list or none at all
+        assert!(parse_vod_list(r#"{"code":1,"msg":"暂无数据","list":null}"#).unwrap().list.unwrap().is_empty());
+        assert!(parse_vod_list(r#"{"code":1,"msg":"暂无数据"}"#).unwrap().list.unwrap().is_empty());
+        assert_eq!(parse_vod_list(r#"{"code":200,"list":null}"#).unwrap_err().kind, HttpErrorKind::Api);
+        // Without a code the list decides
+        assert_eq!(parse_vod_list(r#"{"list":[]}"#).unwrap().code, None);
+
+        let error = parse_vod_list(r#"{"code":-1,"msg":"ip blocked"}"#).unwrap_err();
+        assert_eq!(error.kind, HttpErrorKind::Api);
+        assert_eq!(error.details.as_deref(), Some("ip blocked"));
+        assert_eq!(parse_vod_list("").unwrap_err().kind, HttpErrorKind::Decode);
+    }
+
+    #[test]
+    fn serializes_items_with_source_tags_only_when_set() {
+        let mut item: SearchResultItem = serde_json::from_str(r#"{"vod_id":9,"vod_name":"x"}"#).unwrap();
+        let json = serde_json::to_value(&item).unwrap();
+        assert_eq!(json["vod_id"], "9");
+        assert!(json.get("source_code").is_none());
+
+        item.tag_source("custom", "自定义源", Some("https://api.example.com"));
+        let json = serde_json::to_value(&item).unwrap();
+        assert_eq!(json["source_name"], "自定义源");
+        assert_eq!(json["api_url"], "https://api.example.com");
+    }
+
+    #[test]
+    fn splits_play_lines_and_episodes() {
+        let lines = parse_play_lines(
+            "hnm3u8$$$hnyun",
+            "第01集$https://v.example.com/1.m3u8#第02集$https://v.example.com/2.m3u8$$$正片$https://yun.example.com/share/abc",
+        );
+        assert_eq!(
+            lines,
+            [
+                PlayLine {
+                    name: "hnm3u8".to_string(),
+                    episodes: vec![
+                        Episode { label: "第01集".to_string(), url: "https://v.example.com/1.m3u8".to_string() },
+                        Episode { label: "第02集".to_string(), url: "https://v.example.com/2.m3u8".to_string() },
+                    ],
+                },
+                PlayLine {
+                    name: "hnyun".to_string(),
+                    episodes: vec![Episode { label: "正片".to_string(), url: "https://yun.example.com/share/abc".to_string() }],
+                },
+            ]
+        );
+        assert_eq!(lines[0].urls(), ["https://v.example.com/1.m3u8", "https://v.example.com/2.m3u8"]);
+    }
+
+    #[test]
+    fn labels_episodes_without_label_by_position() {
+        let lines = parse_play_lines("m3u8", "https://v.example.com/1.m3u8#$https://v.example.com/2.m3u8# 第3集 $ https://v.example.com/3.m3u8 ");
+        let labels: Vec<&str> = lines[0].episodes.iter().map(|episode| episode.label.as_str()).collect();
+        assert_eq!(labels, ["第1集", "第2集", "第3集"]);
+        assert_eq!(lines[0].episodes[2].url, "https://v.example.com/3.m3u8");
+    }
+
+    #[test]
+    fn numbers_unlabeled_episodes_among_the_kept_ones() {
+        let lines = parse_play_lines("m3u8", "ftp://x/1.m3u8#正片$rtmp://x/2#https://v.example.com/3.m3u8#https://v.example.com/4.m3u8");
+        let labels: Vec<&str> = lines[0].episodes.iter().map(|episode| episode.label.as_str()).collect();
+        assert_eq!(labels, ["第1集", "第2集"]);
+    }
+
+    #[test]
+    fn skips_empty_lines_and_entries_without_urls() {
+        // Trailing separators, a line of only non-http entries and a missing line name
+        let lines = parse_play_lines(
+            "first$$$flash$$$",
+            "第1集$https://v.example.com/1.m3u8##第2集$https://v.example.com/2.m3u8#$$$第1集$ftp://x/1#第2集$$$第1集$http://v.example.com/b.m3u8",
+        );
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].name, "first");
+        assert_eq!(lines[0].episodes.len(), 2);
+        assert_eq!(lines[0].episodes[1].label, "第2集");
+        // Named by position when vod_play_from has no (or an empty) name for it
+        assert_eq!(lines[1].name, "线路3");
+        assert_eq!(lines[1].episodes[0].url, "http://v.example.com/b.m3u8");
+
+        assert!(parse_play_lines("", "").is_empty());
+        assert!(parse_play_lines("a$$$b", "$$$").is_empty());
+    }
+
+    #[test]
+    fn finds_m3u8_links_in_descriptions() {
+        let content = r#"<p>正片$https://v.example.com/a/index.m3u8</p><a href="$https://v.example.com/b.m3u8">b</a> $https://v.example.com/c.mp4"#;
+        assert_eq!(episodes_from_content(content), ["https://v.example.com/a/index.m3u8", "https://v.example.com/b.m3u8"]);
+        assert!(episodes_from_content("没有链接").is_empty());
+    }
+
+    fn detail(body: &str) -> Result<VideoDetail, HttpError> {
+        parse_video_detail(body, "heimuer", "黑木耳", "https://api.example.com/detail?ids=42".to_string())
+    }
+
+    #[test]
+    fn parses_detail_of_the_first_video() {
+        let body = r#"{"code":1,"msg":"数据列表","page":1,"pagecount":1,"limit":"20","total":1,"list":[{
+            "vod_id":42,"vod_name":"三体","vod_pic":"https://img.example.com/42.jpg","type_name":"国产剧",
+            "vod_year":2023,"vod_area":"大陆","vod_remarks":"已完结","vod_content":"<p>地球往事</p>",
+            "vod_play_from":"hnm3u8$$$hnyun",
+            "vod_play_url":"第01集$https://v.example.com/1/index.m3u8#第02集$https://v.example.com/2/index.m3u8$$$第01集$https://yun.example.com/share/1"
+        }]}"#;
+        let detail = detail(body).unwrap();
+        assert_eq!(detail.code, 200);
+        assert_eq!(detail.episodes, ["https://v.example.com/1/index.m3u8", "https://v.example.com/2/index.m3u8"]);
+        assert_eq!(detail.play_lines.len(), 2);
+        assert_eq!(detail.play_lines[1].name, "hnyun");
+        assert_eq!(detail.detail_url, "https://api.example.com/detail?ids=42");
+        assert_eq!(detail.video_info.title, "三体");
+        assert_eq!(detail.video_info.year, "2023");
+        assert_eq!(detail.video_info.source_code, "heimuer");
+        assert_eq!(detail.video_info.source_name, "黑木耳");
+    }
+
+    #[test]
+    fn prefers_the_first_m3u8_line_for_episodes() {
+        let body = r#"{"code":1,"list":[{"vod_id":42,"vod_name":"三体",
+            "vod_play_from":"hnyun$$$hnm3u8",
+            "vod_play_url":"第01集$https://yun.example.com/share/1$$$第01集$https://v.example.com/1/index.m3u8?sign=abc"
+        }]}"#;
+        let detail = detail(body).unwrap();
+        assert_eq!(detail.episodes, ["https://v.example.com/1/index.m3u8?sign=abc"]);
+        assert_eq!(detail.play_lines[0].name, "hnyun");
+
+        // Without an m3u8 line the first line still provides the episodes
+        let body = body.replace("index.m3u8?sign=abc", "play.html");
+        assert_eq!(detail(&body).unwrap().episodes, ["https://yun.example.com/share/1"]);
+    }
+
+    #[test]
+    fn falls_back_to_links_in_the_description() {
+        let body = r#"{"code":0,"list":[{"vod_id":"7","vod_name":"片名","vod_play_url":"",
+            "vod_content":"正片$https://v.example.com/7/index.m3u8 备用$https://v2.example.com/7/index.m3u8"}]}"#;
+        let detail = detail(body).unwrap();
+        assert!(detail.play_lines.is_empty());
+        assert_eq!(detail.episodes, ["https://v.example.com/7/index.m3u8", "https://v2.example.com/7/index.m3u8"]);
+    }
+
+    #[test]
+    fn reports_missing_videos_as_not_found() {
+        for body in [r#"{"code":1,"msg":"数据列表","list":[]}"#, r#"{"code":1,"msg":"数据列表"}"#, r#"{"code":0,"list":[]}"#] {
+            let error = detail(body).unwrap_err();
+            assert_eq!(error.kind, HttpErrorKind::Api, "{}", body);
+            assert_eq!(error.error, "Video not found");
+        }
+        let error = detail(r#"{"code":0,"msg":"no data"}"#).unwrap_err();
+        assert_eq!(error.kind, HttpErrorKind::Api);
+        assert_eq!(detail("<html>").unwrap_err().kind, HttpErrorKind::Decode);
+    }
+
+    #[test]
+    fn caches_only_usable_api_responses() {
+        let response = |body: &str| HttpResponse {
+            status: 200,
+            headers: Default::default(),
+            body: body.to_string(),
+            body_encoding: crate::BodyEncoding::Text,
+            content_type: Some("application/json".to_string()),
+            content_length: body.len(),
+            attempts: 1,
+            cache: None,
+        };
+        let found = response(r#"{"code":1,"list":[{"vod_id":1,"vod_name":"三体"}]}"#);
+        let empty = response(r#"{"code":1,"list":[]}"#);
+        let failed = response(r#"{"code":-2,"msg":"请求太频繁"}"#);
+        let html = response("<html>维护中</html>");
+
+        assert!(is_list_response(&found) && is_list_response(&empty));
+        assert!(!is_list_response(&failed) && !is_list_response(&html));
+        assert!(is_detail_response(&found));
+        assert!(!is_detail_response(&empty) && !is_detail_response(&failed) && !is_detail_response(&html));
+    }
+}